@@ -0,0 +1,344 @@
+use crate::history;
+use crate::report::{elapsed_seconds, haversine_m, vertical_speeds};
+use crate::{parse_time_of_day, Telemetry};
+use chrono::{DateTime, NaiveTime, Utc};
+use parquet::basic::Compression;
+use parquet::data_type::{
+    BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type,
+};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+/// A packet with typed columns and derived fields, one row of the analysis exports
+#[derive(Debug, Clone, Serialize)]
+struct AnalysisRecord {
+    team_id: i32,
+    /// MISSION_TIME as a full UTC timestamp, needs the receive time for the date
+    mission_time: Option<DateTime<Utc>>,
+    /// MISSION_TIME in seconds since midnight
+    mission_time_s: Option<f64>,
+    /// Seconds since the first packet, continues past midnight
+    elapsed_s: Option<f64>,
+    packet_count: i32,
+    mode: String,
+    state: String,
+    altitude: f64,
+    hs_deployed: bool,
+    pc_deployed: bool,
+    mast_raised: bool,
+    temperature: f64,
+    pressure: f64,
+    voltage: f64,
+    /// GPS_TIME as a full UTC timestamp, needs the receive time for the date
+    gps_time: Option<DateTime<Utc>>,
+    /// GPS fields are empty for packets without a fix instead of zeroed
+    gps_altitude: Option<f64>,
+    gps_latitude: Option<f64>,
+    gps_longitude: Option<f64>,
+    gps_sats: i32,
+    tilt_x: f64,
+    tilt_y: f64,
+    cmd_echo: String,
+    received_utc: Option<DateTime<Utc>>,
+    received_monotonic_s: Option<f64>,
+    /// Change of ALTITUDE per second since the previous packet
+    vertical_speed_m_s: Option<f64>,
+    /// Ground distance from the first GPS fix
+    distance_from_first_fix_m: Option<f64>,
+}
+
+/// Places a time of day on the day closest to `near`, so a packet stamped 23:59:59
+/// but received just after midnight stays on the previous day
+fn timestamp_near(seconds: f64, near: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(
+        seconds.trunc() as u32,
+        (seconds.fract() * 1e9) as u32,
+    )?;
+    let date = near.date_naive();
+    [date.pred_opt(), Some(date), date.succ_opt()]
+        .into_iter()
+        .flatten()
+        .map(|date| date.and_time(time).and_utc())
+        .min_by_key(|candidate| (*candidate - near).num_milliseconds().abs())
+}
+
+fn analysis_records(telemetry: &[Telemetry]) -> Vec<AnalysisRecord> {
+    let elapsed = elapsed_seconds(telemetry);
+    let vertical_speeds = vertical_speeds(telemetry, &elapsed);
+    let first_fix = telemetry.iter().find(|t| t.has_gps_fix());
+
+    telemetry
+        .iter()
+        .zip(elapsed)
+        .zip(vertical_speeds)
+        .map(|((t, elapsed_s), vertical_speed_m_s)| {
+            let has_fix = t.has_gps_fix();
+            let gps = |value: f32| has_fix.then_some(value as f64);
+            let mission_time_s = t.mission_time_seconds();
+            AnalysisRecord {
+                team_id: t.team_id,
+                mission_time: mission_time_s
+                    .zip(t.received_utc)
+                    .and_then(|(s, near)| timestamp_near(s, near)),
+                mission_time_s,
+                elapsed_s,
+                packet_count: t.packet_count,
+                mode: t.mode.clone(),
+                state: t.state.clone(),
+                altitude: t.altitude as f64,
+                hs_deployed: t.hs_deployed == "P",
+                pc_deployed: t.pc_deployed == "C",
+                mast_raised: t.mast_raised == "M",
+                temperature: t.temperature as f64,
+                pressure: t.pressure as f64,
+                voltage: t.voltage as f64,
+                gps_time: parse_time_of_day(&t.gps_time)
+                    .zip(t.received_utc)
+                    .and_then(|(s, near)| timestamp_near(s, near)),
+                gps_altitude: gps(t.gps_altitude),
+                gps_latitude: gps(t.gps_latitude),
+                gps_longitude: gps(t.gps_longitude),
+                gps_sats: t.gps_sats,
+                tilt_x: t.tilt_x as f64,
+                tilt_y: t.tilt_y as f64,
+                cmd_echo: t.cmd_echo.clone(),
+                received_utc: t.received_utc,
+                received_monotonic_s: t.received_monotonic_s,
+                vertical_speed_m_s,
+                distance_from_first_fix_m: first_fix.filter(|_| has_fix).map(|first| {
+                    haversine_m(
+                        first.gps_latitude as f64,
+                        first.gps_longitude as f64,
+                        t.gps_latitude as f64,
+                        t.gps_longitude as f64,
+                    )
+                }),
+            }
+        })
+        .collect()
+}
+
+async fn current_records() -> Result<Vec<AnalysisRecord>, String> {
+    let telemetry = history::whole_flight().await?;
+    if telemetry.is_empty() {
+        return Err("There is no telemetry to export.".to_string());
+    }
+    Ok(analysis_records(&telemetry))
+}
+
+/// Values of one Parquet column, every column is nullable
+enum Column {
+    Int32(Vec<Option<i32>>),
+    Double(Vec<Option<f64>>),
+    Boolean(Vec<Option<bool>>),
+    Text(Vec<Option<String>>),
+    /// Microseconds since the Unix epoch
+    Timestamp(Vec<Option<i64>>),
+}
+
+impl Column {
+    fn schema_type(&self) -> &'static str {
+        match self {
+            Column::Int32(_) => "INT32",
+            Column::Double(_) => "DOUBLE",
+            Column::Boolean(_) => "BOOLEAN",
+            Column::Text(_) => "BYTE_ARRAY",
+            Column::Timestamp(_) => "INT64",
+        }
+    }
+
+    fn logical_type(&self) -> &'static str {
+        match self {
+            Column::Text(_) => " (STRING)",
+            Column::Timestamp(_) => " (TIMESTAMP(MICROS,true))",
+            _ => "",
+        }
+    }
+}
+
+/// Splits nullable values into the present values and their definition levels
+fn levels<T: Clone>(values: &[Option<T>]) -> (Vec<T>, Vec<i16>) {
+    let present = values.iter().flatten().cloned().collect();
+    let definition = values.iter().map(|v| v.is_some() as i16).collect();
+    (present, definition)
+}
+
+fn columns(records: &[AnalysisRecord]) -> Vec<(&'static str, Column)> {
+    let int32 = |f: fn(&AnalysisRecord) -> i32| {
+        Column::Int32(records.iter().map(|r| Some(f(r))).collect())
+    };
+    let double = |f: fn(&AnalysisRecord) -> Option<f64>| {
+        Column::Double(records.iter().map(f).collect())
+    };
+    let boolean = |f: fn(&AnalysisRecord) -> bool| {
+        Column::Boolean(records.iter().map(|r| Some(f(r))).collect())
+    };
+    let text = |f: fn(&AnalysisRecord) -> &str| {
+        Column::Text(records.iter().map(|r| Some(f(r).to_string())).collect())
+    };
+    let timestamp = |f: fn(&AnalysisRecord) -> Option<DateTime<Utc>>| {
+        Column::Timestamp(
+            records
+                .iter()
+                .map(|r| f(r).map(|time| time.timestamp_micros()))
+                .collect(),
+        )
+    };
+
+    vec![
+        ("team_id", int32(|r| r.team_id)),
+        ("mission_time", timestamp(|r| r.mission_time)),
+        ("mission_time_s", double(|r| r.mission_time_s)),
+        ("elapsed_s", double(|r| r.elapsed_s)),
+        ("packet_count", int32(|r| r.packet_count)),
+        ("mode", text(|r| &r.mode)),
+        ("state", text(|r| &r.state)),
+        ("altitude", double(|r| Some(r.altitude))),
+        ("hs_deployed", boolean(|r| r.hs_deployed)),
+        ("pc_deployed", boolean(|r| r.pc_deployed)),
+        ("mast_raised", boolean(|r| r.mast_raised)),
+        ("temperature", double(|r| Some(r.temperature))),
+        ("pressure", double(|r| Some(r.pressure))),
+        ("voltage", double(|r| Some(r.voltage))),
+        ("gps_time", timestamp(|r| r.gps_time)),
+        ("gps_altitude", double(|r| r.gps_altitude)),
+        ("gps_latitude", double(|r| r.gps_latitude)),
+        ("gps_longitude", double(|r| r.gps_longitude)),
+        ("gps_sats", int32(|r| r.gps_sats)),
+        ("tilt_x", double(|r| Some(r.tilt_x))),
+        ("tilt_y", double(|r| Some(r.tilt_y))),
+        ("cmd_echo", text(|r| &r.cmd_echo)),
+        ("received_utc", timestamp(|r| r.received_utc)),
+        ("received_monotonic_s", double(|r| r.received_monotonic_s)),
+        ("vertical_speed_m_s", double(|r| r.vertical_speed_m_s)),
+        (
+            "distance_from_first_fix_m",
+            double(|r| r.distance_from_first_fix_m),
+        ),
+    ]
+}
+
+fn write_parquet(output_file: &str, records: &[AnalysisRecord]) -> Result<(), String> {
+    let parquet_error = |e: parquet::errors::ParquetError| {
+        format!("Error writing Parquet file {}: {}", output_file, e)
+    };
+
+    let columns = columns(records);
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|(name, column)| {
+            format!(
+                "OPTIONAL {} {}{};",
+                column.schema_type(),
+                name,
+                column.logical_type()
+            )
+        })
+        .collect();
+    let schema =
+        parse_message_type(&format!("message telemetry {{ {} }}", fields.join(" ")))
+            .map_err(parquet_error)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    let file = File::create(output_file)
+        .map_err(|e| format!("Error creating {}: {}", output_file, e))?;
+    let mut writer =
+        SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))
+            .map_err(parquet_error)?;
+    let mut row_group = writer.next_row_group().map_err(parquet_error)?;
+    for (_, column) in &columns {
+        let mut column_writer = row_group
+            .next_column()
+            .map_err(parquet_error)?
+            .ok_or_else(|| {
+                "Parquet schema has fewer columns than the data".to_string()
+            })?;
+        let result = match column {
+            Column::Int32(values) => {
+                let (values, definition) = levels(values);
+                column_writer.typed::<Int32Type>().write_batch(
+                    &values,
+                    Some(&definition),
+                    None,
+                )
+            }
+            Column::Timestamp(values) => {
+                let (values, definition) = levels(values);
+                column_writer.typed::<Int64Type>().write_batch(
+                    &values,
+                    Some(&definition),
+                    None,
+                )
+            }
+            Column::Double(values) => {
+                let (values, definition) = levels(values);
+                column_writer.typed::<DoubleType>().write_batch(
+                    &values,
+                    Some(&definition),
+                    None,
+                )
+            }
+            Column::Boolean(values) => {
+                let (values, definition) = levels(values);
+                column_writer.typed::<BoolType>().write_batch(
+                    &values,
+                    Some(&definition),
+                    None,
+                )
+            }
+            Column::Text(values) => {
+                let (values, definition) = levels(values);
+                let values: Vec<ByteArray> =
+                    values.iter().map(|v| ByteArray::from(v.as_str())).collect();
+                column_writer.typed::<ByteArrayType>().write_batch(
+                    &values,
+                    Some(&definition),
+                    None,
+                )
+            }
+        };
+        result.map_err(parquet_error)?;
+        column_writer.close().map_err(parquet_error)?;
+    }
+    row_group.close().map_err(parquet_error)?;
+    writer.close().map_err(parquet_error)?;
+    Ok(())
+}
+
+/// Writes the telemetry as newline delimited JSON, one typed object per packet
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_jsonl(output_file: String) -> Result<usize, String> {
+    let records = current_records().await?;
+    let file = File::create(&output_file)
+        .map_err(|e| format!("Error creating {}: {}", output_file, e))?;
+    let mut writer = BufWriter::new(file);
+    for record in &records {
+        serde_json::to_writer(&mut writer, record)
+            .map_err(|e| format!("Error serializing telemetry: {}", e))?;
+        writer
+            .write_all(b"\n")
+            .map_err(|e| format!("Error writing {}: {}", output_file, e))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Error writing {}: {}", output_file, e))?;
+    println!("Exported {} packets to {}", records.len(), output_file);
+    Ok(records.len())
+}
+
+/// Writes the telemetry as a Snappy compressed Parquet file with the same columns
+/// as the JSON Lines export
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_parquet(output_file: String) -> Result<usize, String> {
+    let records = current_records().await?;
+    write_parquet(&output_file, &records)?;
+    println!("Exported {} packets to {}", records.len(), output_file);
+    Ok(records.len())
+}
@@ -0,0 +1,269 @@
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Rejected lines kept in memory, all of them are in the raw log of the session
+const KEPT_REJECTIONS: usize = 50;
+
+lazy_static! {
+    pub static ref CHECKSUM_CONFIG: Arc<tokio::sync::Mutex<ChecksumConfig>> =
+        Arc::new(tokio::sync::Mutex::new(ChecksumConfig::default()));
+    pub static ref CHECKSUM_STATS: Arc<tokio::sync::Mutex<ChecksumStats>> =
+        Arc::new(tokio::sync::Mutex::new(ChecksumStats::default()));
+}
+
+/// Integrity check appended to a line after a `*`, e.g. `1082,13:14:02,...,CXON*5A`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumKind {
+    #[default]
+    None,
+    /// XOR of all bytes before the `*` as two hex digits, like NMEA sentences
+    Nmea,
+    /// CRC-16/CCITT-FALSE of all bytes before the `*` as four hex digits
+    Crc16,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChecksumConfig {
+    /// Checksum expected on incoming telemetry
+    pub telemetry: ChecksumKind,
+    /// Checksum appended to outgoing commands
+    pub commands: ChecksumKind,
+    /// Rejects telemetry without a checksum instead of accepting it unverified
+    pub required: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChecksumError {
+    Missing,
+    Malformed(String),
+    Mismatch { expected: String, received: String },
+}
+
+impl std::fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChecksumError::Missing => write!(f, "the checksum is missing"),
+            ChecksumError::Malformed(checksum) => {
+                write!(f, "the checksum {:?} is not valid hex", checksum)
+            }
+            ChecksumError::Mismatch { expected, received } => write!(
+                f,
+                "the checksum is {} but the line adds up to {}",
+                received, expected
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RejectedLine {
+    pub time: DateTime<Utc>,
+    pub line: String,
+    pub reason: String,
+}
+
+/// Checksum counters of the current connection
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChecksumStats {
+    pub verified: u64,
+    /// Lines accepted without a checksum because it is not required
+    pub unverified: u64,
+    pub missing: u64,
+    pub malformed: u64,
+    pub mismatched: u64,
+    /// The most recent rejected lines, oldest first
+    pub recent_rejections: VecDeque<RejectedLine>,
+}
+
+impl ChecksumStats {
+    pub fn record_rejection(&mut self, line: &str, error: &ChecksumError) {
+        match error {
+            ChecksumError::Missing => self.missing += 1,
+            ChecksumError::Malformed(_) => self.malformed += 1,
+            ChecksumError::Mismatch { .. } => self.mismatched += 1,
+        }
+        if self.recent_rejections.len() == KEPT_REJECTIONS {
+            self.recent_rejections.pop_front();
+        }
+        self.recent_rejections.push_back(RejectedLine {
+            time: Utc::now(),
+            line: line.to_string(),
+            reason: error.to_string(),
+        });
+    }
+}
+
+fn nmea(payload: &[u8]) -> u8 {
+    payload.iter().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+fn crc16(payload: &[u8]) -> u16 {
+    payload.iter().fold(0xFFFF, |mut crc: u16, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// The checksum of a payload as it is written after the `*`
+fn checksum(payload: &str, kind: ChecksumKind) -> Option<String> {
+    match kind {
+        ChecksumKind::None => None,
+        ChecksumKind::Nmea => Some(format!("{:02X}", nmea(payload.as_bytes()))),
+        ChecksumKind::Crc16 => Some(format!("{:04X}", crc16(payload.as_bytes()))),
+    }
+}
+
+/// Checks the checksum of a received line and returns the line without it. A line
+/// without a checksum is returned as is when `required` is false
+pub fn verify(
+    line: &str,
+    kind: ChecksumKind,
+    required: bool,
+) -> Result<&str, ChecksumError> {
+    if kind == ChecksumKind::None {
+        return Ok(line);
+    }
+    let (payload, received) = match line.rsplit_once('*') {
+        Some(split) => split,
+        None if required => return Err(ChecksumError::Missing),
+        None => return Ok(line),
+    };
+    let received = received.trim();
+    let digits = if kind == ChecksumKind::Nmea { 2 } else { 4 };
+    if received.len() != digits || !received.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ChecksumError::Malformed(received.to_string()));
+    }
+    let expected = checksum(payload, kind).unwrap_or_default();
+    if !expected.eq_ignore_ascii_case(received) {
+        return Err(ChecksumError::Mismatch {
+            expected,
+            received: received.to_string(),
+        });
+    }
+    Ok(payload)
+}
+
+/// Appends the checksum to an outgoing line, without the line ending
+pub fn append(line: &str, kind: ChecksumKind) -> String {
+    match checksum(line, kind) {
+        Some(checksum) => format!("{}*{}", line, checksum),
+        None => line.to_string(),
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_checksum_config(config: ChecksumConfig) -> Result<(), String> {
+    if config.required && config.telemetry == ChecksumKind::None {
+        return Err(
+            "A checksum can only be required once its kind is chosen.".to_string()
+        );
+    }
+    println!("New checksum config: {:?}", config);
+    *CHECKSUM_CONFIG.lock().await = config;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_checksum_config() -> ChecksumConfig {
+    CHECKSUM_CONFIG.lock().await.clone()
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_checksum_stats() -> ChecksumStats {
+    CHECKSUM_STATS.lock().await.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &str = "GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,";
+
+    #[test]
+    fn computes_the_reference_checksums() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(nmea(GGA.as_bytes()), 0x47);
+        assert_eq!(checksum(GGA, ChecksumKind::Nmea).as_deref(), Some("47"));
+        assert_eq!(
+            checksum("123456789", ChecksumKind::Crc16).as_deref(),
+            Some("29B1")
+        );
+        assert_eq!(checksum("123456789", ChecksumKind::None), None);
+    }
+
+    #[test]
+    fn verifies_what_append_writes() {
+        for kind in [ChecksumKind::Nmea, ChecksumKind::Crc16] {
+            let line = append("CMD,1082,CX,ON", kind);
+            assert_eq!(verify(&line, kind, true), Ok("CMD,1082,CX,ON"));
+        }
+        assert_eq!(
+            append("CMD,1082,CX,ON", ChecksumKind::None),
+            "CMD,1082,CX,ON"
+        );
+        assert_eq!(
+            verify(&format!("{}*47", GGA), ChecksumKind::Nmea, true),
+            Ok(GGA)
+        );
+        assert_eq!(
+            verify("123456789*29b1\r", ChecksumKind::Crc16, true),
+            Ok("123456789")
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_malformed_and_missing_checksums() {
+        assert_eq!(
+            verify("123456789*29B2", ChecksumKind::Crc16, false),
+            Err(ChecksumError::Mismatch {
+                expected: "29B1".to_string(),
+                received: "29B2".to_string(),
+            })
+        );
+        assert_eq!(
+            verify("123456789*4", ChecksumKind::Nmea, false),
+            Err(ChecksumError::Malformed("4".to_string()))
+        );
+        assert_eq!(
+            verify("123456789*ZZ", ChecksumKind::Nmea, false),
+            Err(ChecksumError::Malformed("ZZ".to_string()))
+        );
+        assert_eq!(
+            verify("123456789", ChecksumKind::Nmea, true),
+            Err(ChecksumError::Missing)
+        );
+    }
+
+    #[test]
+    fn accepts_lines_without_a_checksum_unless_required() {
+        assert_eq!(
+            verify("123456789", ChecksumKind::Nmea, false),
+            Ok("123456789")
+        );
+        assert_eq!(verify("1*2", ChecksumKind::None, true), Ok("1*2"));
+    }
+
+    #[test]
+    fn counts_rejections_and_keeps_the_most_recent() {
+        let mut stats = ChecksumStats::default();
+        for i in 0..KEPT_REJECTIONS + 2 {
+            stats.record_rejection(&format!("line {}", i), &ChecksumError::Missing);
+        }
+        stats.record_rejection("bad", &ChecksumError::Malformed("Z".to_string()));
+        assert_eq!(stats.missing, KEPT_REJECTIONS as u64 + 2);
+        assert_eq!(stats.malformed, 1);
+        assert_eq!(stats.recent_rejections.len(), KEPT_REJECTIONS);
+        assert_eq!(stats.recent_rejections.back().unwrap().line, "bad");
+    }
+}
@@ -0,0 +1,349 @@
+use crate::link_watchdog::LinkStatus;
+use crate::packet_types::PacketType;
+use crate::transport::Framing;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncWriteExt, WriteHalf};
+use tokio::sync::oneshot;
+use tokio_serial::SerialStream;
+
+/// Packets remembered to recognise the copies received by redundant radios
+const DEDUPLICATION_WINDOW: usize = 256;
+
+lazy_static! {
+    static ref CONNECTIONS: Arc<tokio::sync::Mutex<Connections>> =
+        Arc::new(tokio::sync::Mutex::new(Connections::default()));
+    static ref RECEIVED_PACKETS: Arc<tokio::sync::Mutex<VecDeque<PacketKey>>> =
+        Arc::new(tokio::sync::Mutex::new(VecDeque::new()));
+}
+
+/// What is connected on the other end
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionKind {
+    /// A radio receiving the telemetry of the probe
+    #[default]
+    Telemetry,
+    /// A GPS receiver giving the position of the ground station, see `ground_gps`
+    GroundGps,
+}
+
+/// State of an open connection, listed by `list_connections` and sent with the
+/// `connections-changed` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionStatus {
+    pub id: String,
+    pub kind: ConnectionKind,
+    pub device: String,
+    pub baudrate: i32,
+    pub framing: Framing,
+    /// Commands go to the primary connection unless another one is given
+    pub primary: bool,
+    pub connected_at: DateTime<Utc>,
+    /// Valid packets received on this connection, including the ones another
+    /// connection received first
+    pub packets_received: u64,
+    /// Packets that another connection had already received
+    pub duplicates: u64,
+    pub packets_rejected: u64,
+    pub last_packet_utc: Option<DateTime<Utc>>,
+    /// Signal strength of the last frame received on this connection, duplicates
+    /// included
+    pub last_rssi_dbm: Option<i16>,
+    pub link_status: Option<LinkStatus>,
+}
+
+struct Connection {
+    status: ConnectionStatus,
+    /// Locked on its own, a slow write must not hold up the other connections
+    sender: Arc<tokio::sync::Mutex<WriteHalf<SerialStream>>>,
+    /// Tells the read task to close the connection
+    stop: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Default)]
+struct Connections {
+    connections: BTreeMap<String, Connection>,
+    primary: Option<String>,
+    /// When the first of the open connections was opened
+    opened_at: Option<Instant>,
+}
+
+/// An opened connection as its read task needs it
+pub struct Registration {
+    /// Resolves when the connection is to be closed
+    pub stopped: oneshot::Receiver<()>,
+    /// Whether no other telemetry connection was open
+    pub first: bool,
+    /// When the first of the open connections was opened, the receive times of all
+    /// of them count from it
+    pub opened_at: Instant,
+}
+
+impl Connections {
+    fn resolve(&mut self, id: Option<&str>) -> Result<&mut Connection, String> {
+        let id = match id.or(self.primary.as_deref()) {
+            Some(id) => id.to_string(),
+            None => return Err("No connected device found.".to_string()),
+        };
+        let connection = self
+            .connections
+            .get_mut(&id)
+            .ok_or(format!("No connection with the ID {:?}", id))?;
+        if connection.status.kind != ConnectionKind::Telemetry {
+            return Err(format!("{} is not connected to a radio.", id));
+        }
+        Ok(connection)
+    }
+
+    fn telemetry_ids(&self) -> impl Iterator<Item = &String> {
+        self.connections
+            .values()
+            .filter(|connection| connection.status.kind == ConnectionKind::Telemetry)
+            .map(|connection| &connection.status.id)
+    }
+
+    fn check_available(&self, id: &str, device: &str) -> Result<(), String> {
+        if self.connections.contains_key(id) {
+            return Err(format!(
+                "A connection with the ID {:?} is already open.",
+                id
+            ));
+        }
+        if self
+            .connections
+            .values()
+            .any(|connection| connection.status.device == device)
+        {
+            return Err(format!("{} is already connected.", device));
+        }
+        Ok(())
+    }
+
+    fn statuses(&self) -> Vec<ConnectionStatus> {
+        self.connections
+            .values()
+            .map(|connection| {
+                let mut status = connection.status.clone();
+                status.primary = self.primary.as_ref() == Some(&status.id);
+                status
+            })
+            .collect()
+    }
+}
+
+/// Identifies a packet across connections
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PacketKey {
+    packet_type: PacketType,
+    packet_count: i32,
+    mission_time: String,
+}
+
+/// Fails if the ID or the device is already taken by an open connection
+pub async fn check_available(id: &str, device: &str) -> Result<(), String> {
+    CONNECTIONS.lock().await.check_available(id, device)
+}
+
+/// Whether a connection is open on the device
+pub async fn is_connected(device: &str) -> bool {
+    CONNECTIONS
+        .lock()
+        .await
+        .connections
+        .values()
+        .any(|connection| connection.status.device == device)
+}
+
+/// Adds an opened connection, the first telemetry connection becomes the primary
+/// connection
+pub async fn register(
+    id: &str,
+    kind: ConnectionKind,
+    device: &str,
+    baudrate: i32,
+    framing: Framing,
+    sender: WriteHalf<SerialStream>,
+) -> Result<Registration, String> {
+    let mut connections = CONNECTIONS.lock().await;
+    connections.check_available(id, device)?;
+    let (stop, stopped) = oneshot::channel();
+    let first = connections.telemetry_ids().next().is_none();
+    if kind == ConnectionKind::Telemetry {
+        if first {
+            RECEIVED_PACKETS.lock().await.clear();
+            connections.opened_at = Some(Instant::now());
+        }
+        connections.primary.get_or_insert_with(|| id.to_string());
+    }
+    let opened_at = *connections.opened_at.get_or_insert_with(Instant::now);
+    connections.connections.insert(
+        id.to_string(),
+        Connection {
+            status: ConnectionStatus {
+                id: id.to_string(),
+                kind,
+                device: device.to_string(),
+                baudrate,
+                framing,
+                primary: false,
+                connected_at: Utc::now(),
+                packets_received: 0,
+                duplicates: 0,
+                packets_rejected: 0,
+                last_packet_utc: None,
+                last_rssi_dbm: None,
+                link_status: None,
+            },
+            sender: Arc::new(tokio::sync::Mutex::new(sender)),
+            stop: Some(stop),
+        },
+    );
+    Ok(Registration {
+        stopped,
+        first,
+        opened_at,
+    })
+}
+
+/// Drops a connection whose read task ended, the next remaining telemetry
+/// connection takes over as primary. Returns whether it was the last telemetry
+/// connection
+pub async fn remove(id: &str) -> bool {
+    let mut connections = CONNECTIONS.lock().await;
+    let removed = connections.connections.remove(id);
+    if connections.primary.as_deref() == Some(id) {
+        let next = connections.telemetry_ids().next().cloned();
+        connections.primary = next;
+    }
+    removed
+        .is_some_and(|connection| connection.status.kind == ConnectionKind::Telemetry)
+        && connections.telemetry_ids().next().is_none()
+}
+
+/// The ID of the connection commands are sent to by default
+pub async fn primary_id() -> Option<String> {
+    CONNECTIONS.lock().await.primary.clone()
+}
+
+/// The ID and framing of a connection, by default the primary one
+pub async fn resolve(id: Option<&str>) -> Result<(String, Framing), String> {
+    let mut connections = CONNECTIONS.lock().await;
+    let connection = connections.resolve(id)?;
+    Ok((connection.status.id.clone(), connection.status.framing))
+}
+
+/// Writes raw bytes to a connection, by default the primary one
+pub async fn write(id: Option<&str>, bytes: &[u8]) -> Result<(), String> {
+    let sender = CONNECTIONS.lock().await.resolve(id)?.sender.clone();
+    let mut sender = sender.lock().await;
+    sender
+        .write_all(bytes)
+        .await
+        .map_err(|e| format!("Failed to write to port: {}", e))
+}
+
+/// Counts a packet of a connection, `accepted` is false for rejected lines
+pub async fn count_packet(id: &str, accepted: bool) {
+    if let Some(connection) = CONNECTIONS.lock().await.connections.get_mut(id) {
+        if accepted {
+            connection.status.packets_received += 1;
+            connection.status.last_packet_utc = Some(Utc::now());
+        } else {
+            connection.status.packets_rejected += 1;
+        }
+    }
+}
+
+/// Keeps the signal strength of the last frame received on a connection
+pub async fn set_rssi(id: &str, rssi_dbm: i16) {
+    if let Some(connection) = CONNECTIONS.lock().await.connections.get_mut(id) {
+        connection.status.last_rssi_dbm = Some(rssi_dbm);
+    }
+}
+
+/// Whether no connection received the packet before, otherwise it is counted as a
+/// duplicate of the given connection
+pub async fn first_reception(
+    id: &str,
+    packet_type: PacketType,
+    packet_count: i32,
+    mission_time: &str,
+) -> bool {
+    let key = PacketKey {
+        packet_type,
+        packet_count,
+        mission_time: mission_time.to_string(),
+    };
+    let mut received = RECEIVED_PACKETS.lock().await;
+    if received.contains(&key) {
+        drop(received);
+        if let Some(connection) = CONNECTIONS.lock().await.connections.get_mut(id) {
+            connection.status.duplicates += 1;
+        }
+        return false;
+    }
+    received.push_back(key);
+    while received.len() > DEDUPLICATION_WINDOW {
+        received.pop_front();
+    }
+    true
+}
+
+/// Updates the link status of a connection
+pub async fn set_link_status(id: &str, status: LinkStatus) {
+    if let Some(connection) = CONNECTIONS.lock().await.connections.get_mut(id) {
+        connection.status.link_status = Some(status);
+    }
+}
+
+/// Sends the open connections to the UI as a `connections-changed` event
+pub async fn emit_changed(app_handle: &AppHandle) {
+    let statuses = CONNECTIONS.lock().await.statuses();
+    app_handle
+        .emit_all("connections-changed", statuses)
+        .expect("failed to emit event");
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_connections() -> Vec<ConnectionStatus> {
+    CONNECTIONS.lock().await.statuses()
+}
+
+/// Makes a connection the one commands are sent to by default
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_primary_connection(
+    app_handle: AppHandle,
+    connection_id: String,
+) -> Result<(), String> {
+    {
+        let mut connections = CONNECTIONS.lock().await;
+        connections.resolve(Some(&connection_id))?;
+        println!("Primary connection: {}", connection_id);
+        connections.primary = Some(connection_id);
+    }
+    emit_changed(&app_handle).await;
+    Ok(())
+}
+
+/// Closes a connection, its read task writes what it still holds and removes it
+#[tauri::command(rename_all = "snake_case")]
+pub async fn close_connection(connection_id: String) -> Result<(), String> {
+    let mut connections = CONNECTIONS.lock().await;
+    let stop = connections
+        .connections
+        .get_mut(&connection_id)
+        .ok_or(format!("No connection with the ID {:?}", connection_id))?
+        .stop
+        .take();
+    if let Some(stop) = stop {
+        println!("Closing connection {}", connection_id);
+        let _ = stop.send(());
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use crate::Telemetry;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+lazy_static! {
+    pub static ref EMISSION_CONFIG: Arc<tokio::sync::Mutex<EmissionConfig>> =
+        Arc::new(tokio::sync::Mutex::new(EmissionConfig::default()));
+}
+
+/// How received packets are sent to the UI. Logging and storage are not affected and
+/// always happen for every packet as it arrives
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmissionConfig {
+    /// Interval in milliseconds at which pending packets are sent as one batch. Only
+    /// applied when a new connection is started
+    pub batch_interval_ms: u64,
+    /// A batch is sent right away once it holds this many packets, 1 sends every
+    /// packet on its own
+    pub max_batch_size: usize,
+}
+
+impl Default for EmissionConfig {
+    fn default() -> Self {
+        // 10 Hz is plenty for the plots
+        EmissionConfig {
+            batch_interval_ms: 100,
+            max_batch_size: 50,
+        }
+    }
+}
+
+/// Payload of the `graph-data` and `payload-data` events
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TelemetryBatch<T = Telemetry> {
+    /// The connection the packets were received on
+    pub connection_id: String,
+    /// Increases by one with every batch of the connection, starting at 0, so the UI
+    /// can tell when it missed one
+    pub sequence: u64,
+    pub packets: Vec<T>,
+}
+
+/// Collects the packets of a connection until they are due, owned by its read task
+pub struct TelemetryBatcher<T = Telemetry> {
+    connection_id: String,
+    sequence: u64,
+    pending: Vec<T>,
+}
+
+impl<T> TelemetryBatcher<T> {
+    pub fn new(connection_id: &str) -> Self {
+        TelemetryBatcher {
+            connection_id: connection_id.to_string(),
+            sequence: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Queues a packet, returns the batch if it is full and has to be sent now
+    pub fn push(
+        &mut self,
+        packet: T,
+        config: &EmissionConfig,
+    ) -> Option<TelemetryBatch<T>> {
+        self.pending.push(packet);
+        if self.pending.len() >= config.max_batch_size {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    /// Takes the pending packets as the next batch, `None` if nothing is pending
+    pub fn take(&mut self) -> Option<TelemetryBatch<T>> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let batch = TelemetryBatch {
+            connection_id: self.connection_id.clone(),
+            sequence: self.sequence,
+            packets: std::mem::take(&mut self.pending),
+        };
+        self.sequence += 1;
+        Some(batch)
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_emission_config(config: EmissionConfig) -> Result<(), String> {
+    if config.batch_interval_ms < 10 {
+        return Err("The batch interval must be at least 10 ms.".to_string());
+    }
+    if config.max_batch_size == 0 {
+        return Err("The batch size must be greater than zero.".to_string());
+    }
+    println!("New emission config: {:?}", config);
+    *EMISSION_CONFIG.lock().await = config;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_emission_config() -> EmissionConfig {
+    EMISSION_CONFIG.lock().await.clone()
+}
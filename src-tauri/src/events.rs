@@ -0,0 +1,77 @@
+use crate::history::HISTORY_CONFIG;
+use crate::store;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+lazy_static! {
+    pub static ref SENT_COMMANDS: Arc<tokio::sync::Mutex<Vec<SentCommand>>> =
+        Arc::new(tokio::sync::Mutex::new(vec![]));
+    pub static ref ALARMS: Arc<tokio::sync::Mutex<Vec<Alarm>>> =
+        Arc::new(tokio::sync::Mutex::new(vec![]));
+}
+
+/// A command that was successfully written to the device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SentCommand {
+    pub time: DateTime<Utc>,
+    /// The command without the trailing line ending, e.g. CMD,1082,CX,ON
+    pub command: String,
+}
+
+/// Something the operator should have been warned about during the session
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alarm {
+    pub time: DateTime<Utc>,
+    /// Short machine readable name of the alarm, e.g. link_silent
+    pub kind: String,
+    pub message: String,
+}
+
+/// Drops the oldest entries beyond the history retention, the store keeps all of them
+fn enforce_retention<T>(entries: &mut Vec<T>, max_entries: usize) {
+    if entries.len() > max_entries {
+        let excess = entries.len() - max_entries;
+        entries.drain(..excess);
+    }
+}
+
+/// Forgets the commands and alarms of the previous session when a new one starts
+pub async fn clear() {
+    SENT_COMMANDS.lock().await.clear();
+    ALARMS.lock().await.clear();
+}
+
+/// Replaces the commands in memory, e.g. with the ones of a recovered session
+pub async fn replace_commands(mut commands: Vec<SentCommand>) {
+    let max_entries = HISTORY_CONFIG.lock().await.max_packets;
+    enforce_retention(&mut commands, max_entries);
+    *SENT_COMMANDS.lock().await = commands;
+}
+
+pub async fn record_command(command: &str) {
+    let command = SentCommand {
+        time: Utc::now(),
+        command: command.to_string(),
+    };
+    store::insert_command(&command).await;
+    let max_entries = HISTORY_CONFIG.lock().await.max_packets;
+    let mut commands = SENT_COMMANDS.lock().await;
+    commands.push(command);
+    enforce_retention(&mut commands, max_entries);
+}
+
+pub async fn record_alarm(kind: &str, message: String) {
+    println!("Alarm {}: {}", kind, message);
+    let alarm = Alarm {
+        time: Utc::now(),
+        kind: kind.to_string(),
+        message,
+    };
+    store::insert_event(&alarm).await;
+    let max_entries = HISTORY_CONFIG.lock().await.max_packets;
+    let mut alarms = ALARMS.lock().await;
+    alarms.push(alarm);
+    enforce_retention(&mut alarms, max_entries);
+}
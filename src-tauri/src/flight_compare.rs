@@ -0,0 +1,297 @@
+use crate::report::{elapsed_seconds, vertical_speeds, Sensor};
+use crate::session::{self, TELEMETRY_LOG_FILE};
+use crate::telemetry_schema::{TelemetrySchema, TELEMETRY_SCHEMA};
+use crate::Telemetry;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Rise above the first reported altitude that counts as the launch
+const LAUNCH_THRESHOLD_M: f64 = 10.0;
+
+/// Fields returned as aligned series
+const COMPARED_FIELDS: [Sensor; 6] = [
+    ("altitude", "m", |t| t.altitude as f64),
+    ("temperature", "°C", |t| t.temperature as f64),
+    ("pressure", "kPa", |t| t.pressure as f64),
+    ("voltage", "V", |t| t.voltage as f64),
+    ("tilt_x", "°", |t| t.tilt_x as f64),
+    ("tilt_y", "°", |t| t.tilt_y as f64),
+];
+
+/// Event every flight is shifted to so that it happens at time zero
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlightAlignment {
+    /// First packet more than `LAUNCH_THRESHOLD_M` above the first reported altitude
+    Launch,
+    /// Packet with the highest altitude
+    Apogee,
+    /// First packet of the flight, i.e. plain elapsed mission time
+    MissionTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateTransition {
+    pub state: String,
+    /// Aligned time at which the state was first reported
+    pub time_s: f64,
+}
+
+/// A named series of values against aligned time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlignedSeries {
+    pub field: String,
+    pub unit: String,
+    pub values: Vec<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FlightSummary {
+    /// Session ID or file the flight was loaded from
+    pub source: String,
+    pub name: String,
+    pub packets: usize,
+    /// Elapsed mission time of the alignment event, subtracted from every time below
+    pub alignment_offset_s: f64,
+    pub launch_time_s: Option<f64>,
+    pub apogee_time_s: f64,
+    pub apogee_m: f64,
+    /// Mean descent rate from apogee back down to launch altitude, or to the last
+    /// packet if the flight never got that low
+    pub mean_descent_rate_m_s: Option<f64>,
+    pub max_descent_rate_m_s: Option<f64>,
+    pub duration_s: f64,
+    pub state_transitions: Vec<StateTransition>,
+    /// Aligned time of every packet, shared by all series
+    pub time_s: Vec<f64>,
+    pub series: Vec<AlignedSeries>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateTransitionDelta {
+    pub state: String,
+    pub delta_s: f64,
+}
+
+/// Difference of a flight to the reference flight, positive when the flight has
+/// the larger value
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FlightDelta {
+    pub source: String,
+    pub apogee_delta_m: f64,
+    pub apogee_time_delta_s: f64,
+    pub mean_descent_rate_delta_m_s: Option<f64>,
+    pub max_descent_rate_delta_m_s: Option<f64>,
+    pub duration_delta_s: f64,
+    /// Timing of the states reported by both flights
+    pub state_transition_deltas: Vec<StateTransitionDelta>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FlightComparison {
+    pub alignment: FlightAlignment,
+    pub flights: Vec<FlightSummary>,
+    /// Differences of every other flight to the first one
+    pub deltas: Vec<FlightDelta>,
+    /// Flights that could not be aligned as requested and fell back to their first
+    /// packet
+    pub warnings: Vec<String>,
+}
+
+/// Reads a flight from a CSV file or, if no such file exists, from a recorded session
+fn load_flight(
+    source: &str,
+    schema: &TelemetrySchema,
+) -> Result<(String, Vec<Telemetry>), String> {
+    let path = Path::new(source);
+    let (name, csv_path) = if path.is_file() {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| source.to_string());
+        (name, path.to_path_buf())
+    } else {
+        let directory = session::session_directory(source)?;
+        let name = session::read_manifest(&directory)?.name;
+        (name, directory.join(TELEMETRY_LOG_FILE))
+    };
+    let (telemetry, skipped) = schema.read_csv(&csv_path)?;
+    if skipped > 0 {
+        println!("Skipped {} damaged rows of {:?}", skipped, csv_path);
+    }
+    Ok((name, telemetry))
+}
+
+fn summarise(
+    source: &str,
+    name: String,
+    telemetry: &[Telemetry],
+    alignment: FlightAlignment,
+    warnings: &mut Vec<String>,
+) -> Result<FlightSummary, String> {
+    // Packets without a usable MISSION_TIME cannot be placed on the time axis
+    let elapsed = elapsed_seconds(telemetry);
+    let speeds = vertical_speeds(telemetry, &elapsed);
+    let packets: Vec<(&Telemetry, f64, Option<f64>)> = telemetry
+        .iter()
+        .zip(elapsed)
+        .zip(speeds)
+        .filter_map(|((t, elapsed), speed)| Some((t, elapsed?, speed)))
+        .collect();
+    let (first, last) = match (packets.first(), packets.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            return Err(format!(
+                "Flight {} has no packets with a mission time",
+                source
+            ))
+        }
+    };
+
+    let ground_m = first.0.altitude as f64;
+    let launch = packets
+        .iter()
+        .position(|(t, _, _)| t.altitude as f64 > ground_m + LAUNCH_THRESHOLD_M);
+    let apogee = packets
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.0.altitude.total_cmp(&b.0.altitude))
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+
+    let offset = match alignment {
+        FlightAlignment::Launch => match launch {
+            Some(launch) => packets[launch].1,
+            None => {
+                warnings.push(format!(
+                    "No launch detected in {}, aligned on its first packet",
+                    name
+                ));
+                first.1
+            }
+        },
+        FlightAlignment::Apogee => packets[apogee].1,
+        FlightAlignment::MissionTime => first.1,
+    };
+
+    let descent = &packets[apogee..];
+    let landing = descent
+        .iter()
+        .position(|(t, _, _)| t.altitude as f64 <= ground_m + LAUNCH_THRESHOLD_M)
+        .map(|index| &descent[index])
+        .unwrap_or(last);
+    let apogee_packet = &packets[apogee];
+    let mean_descent_rate_m_s = (landing.1 > apogee_packet.1).then(|| {
+        (apogee_packet.0.altitude - landing.0.altitude) as f64
+            / (landing.1 - apogee_packet.1)
+    });
+    let max_descent_rate_m_s = descent
+        .iter()
+        .filter_map(|(_, _, speed)| speed.map(|speed| -speed))
+        .max_by(f64::total_cmp);
+
+    let mut state_transitions: Vec<StateTransition> = Vec::new();
+    for (t, elapsed, _) in &packets {
+        if !state_transitions.iter().any(|s| s.state == t.state) {
+            state_transitions.push(StateTransition {
+                state: t.state.clone(),
+                time_s: elapsed - offset,
+            });
+        }
+    }
+
+    Ok(FlightSummary {
+        source: source.to_string(),
+        name,
+        packets: packets.len(),
+        alignment_offset_s: offset,
+        launch_time_s: launch.map(|launch| packets[launch].1 - offset),
+        apogee_time_s: apogee_packet.1 - offset,
+        apogee_m: apogee_packet.0.altitude as f64,
+        mean_descent_rate_m_s,
+        max_descent_rate_m_s,
+        duration_s: last.1 - first.1,
+        state_transitions,
+        time_s: packets
+            .iter()
+            .map(|(_, elapsed, _)| elapsed - offset)
+            .collect(),
+        series: COMPARED_FIELDS
+            .iter()
+            .map(|(field, unit, value)| AlignedSeries {
+                field: field.to_string(),
+                unit: unit.to_string(),
+                values: packets.iter().map(|(t, _, _)| value(t)).collect(),
+            })
+            .collect(),
+    })
+}
+
+fn delta(reference: &FlightSummary, flight: &FlightSummary) -> FlightDelta {
+    let difference = |a: Option<f64>, b: Option<f64>| Some(a? - b?);
+    FlightDelta {
+        source: flight.source.clone(),
+        apogee_delta_m: flight.apogee_m - reference.apogee_m,
+        apogee_time_delta_s: flight.apogee_time_s - reference.apogee_time_s,
+        mean_descent_rate_delta_m_s: difference(
+            flight.mean_descent_rate_m_s,
+            reference.mean_descent_rate_m_s,
+        ),
+        max_descent_rate_delta_m_s: difference(
+            flight.max_descent_rate_m_s,
+            reference.max_descent_rate_m_s,
+        ),
+        duration_delta_s: flight.duration_s - reference.duration_s,
+        state_transition_deltas: flight
+            .state_transitions
+            .iter()
+            .filter_map(|transition| {
+                let reference = reference
+                    .state_transitions
+                    .iter()
+                    .find(|r| r.state == transition.state)?;
+                Some(StateTransitionDelta {
+                    state: transition.state.clone(),
+                    delta_s: transition.time_s - reference.time_s,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Loads two or more flights, given as session IDs or CSV files, and returns their
+/// series on a common time axis together with the differences to the first flight
+#[tauri::command(rename_all = "snake_case")]
+pub async fn compare_flights(
+    sources: Vec<String>,
+    alignment: FlightAlignment,
+) -> Result<FlightComparison, String> {
+    if sources.len() < 2 {
+        return Err("At least two flights are needed for a comparison.".to_string());
+    }
+
+    let mut warnings = Vec::new();
+    let mut flights = Vec::new();
+    let schema = TELEMETRY_SCHEMA.lock().await.clone();
+    for source in &sources {
+        let (name, telemetry) = load_flight(source, &schema)?;
+        flights.push(summarise(
+            source,
+            name,
+            &telemetry,
+            alignment,
+            &mut warnings,
+        )?);
+    }
+    let deltas = flights[1..]
+        .iter()
+        .map(|flight| delta(&flights[0], flight))
+        .collect();
+
+    Ok(FlightComparison {
+        alignment,
+        flights,
+        deltas,
+        warnings,
+    })
+}
@@ -0,0 +1,516 @@
+use crate::checksum::{self, ChecksumKind};
+use crate::connections::{self, ConnectionKind};
+use crate::line_codec::{Frame, TelemetryLineCodec, MAX_LINE_LENGTH};
+use crate::report::{haversine_m, EARTH_RADIUS_M};
+use crate::serial_config::SerialSettings;
+use crate::transport::Framing;
+use crate::Telemetry;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::io::split;
+use tokio_serial::SerialPortBuilderExt;
+use tokio_util::codec::FramedRead;
+
+lazy_static! {
+    static ref GROUND_GPS: Arc<tokio::sync::Mutex<GroundGps>> =
+        Arc::new(tokio::sync::Mutex::new(GroundGps::default()));
+}
+
+/// Position of the ground station from its own GPS receiver, payload of the
+/// `ground-position` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroundPosition {
+    /// Decimal degrees, negative south of the equator
+    pub latitude: f64,
+    /// Decimal degrees, negative west of Greenwich
+    pub longitude: f64,
+    /// Meters above mean sea level, like GPS_ALTITUDE of the probe
+    pub altitude: f64,
+    pub satellites: Option<u32>,
+    /// Horizontal dilution of precision, lower is better
+    pub hdop: Option<f64>,
+    /// UTC time of the fix as reported by the receiver, hhmmss.ss
+    pub fix_time: String,
+    pub received_utc: DateTime<Utc>,
+}
+
+/// Last GPS position of the probe
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProbePosition {
+    pub packet_count: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+    pub received_utc: Option<DateTime<Utc>>,
+}
+
+/// Where to point the directional antenna, payload of the `antenna-pointing` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AntennaPointing {
+    /// Straight line distance from the station to the probe in meters
+    pub range_m: f64,
+    /// Distance along the ground in meters
+    pub ground_distance_m: f64,
+    /// Degrees clockwise from true north
+    pub bearing_deg: f64,
+    /// Degrees above the horizon of the station, corrected for the curvature of the
+    /// Earth
+    pub elevation_deg: f64,
+    /// Seconds since the fix of the station was received, the pointing is only as
+    /// current as the older of the two fixes
+    pub station_fix_age_s: f64,
+    /// Seconds since the fix of the probe was received, `None` for packets without a
+    /// receive time
+    pub probe_fix_age_s: Option<f64>,
+    pub station: GroundPosition,
+    pub probe: ProbePosition,
+}
+
+/// Seconds from `received` until `now`
+fn age_s(received: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    (now - received).num_milliseconds() as f64 / 1000.0
+}
+
+impl AntennaPointing {
+    fn new(
+        station: &GroundPosition,
+        probe: &ProbePosition,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let ground_distance_m = haversine_m(
+            station.latitude,
+            station.longitude,
+            probe.latitude,
+            probe.longitude,
+        );
+        let lat_station = station.latitude.to_radians();
+        let lat_probe = probe.latitude.to_radians();
+        let d_lon = (probe.longitude - station.longitude).to_radians();
+        let bearing = (d_lon.sin() * lat_probe.cos()).atan2(
+            lat_station.cos() * lat_probe.sin()
+                - lat_station.sin() * lat_probe.cos() * d_lon.cos(),
+        );
+        // The farther the probe, the more it sinks below the horizontal plane of the
+        // station
+        let drop = ground_distance_m.powi(2) / (2.0 * EARTH_RADIUS_M);
+        let height = probe.altitude - station.altitude - drop;
+        AntennaPointing {
+            range_m: ground_distance_m.hypot(height),
+            ground_distance_m,
+            bearing_deg: bearing.to_degrees().rem_euclid(360.0),
+            elevation_deg: height.atan2(ground_distance_m).to_degrees(),
+            station_fix_age_s: age_s(station.received_utc, now),
+            probe_fix_age_s: probe.received_utc.map(|received| age_s(received, now)),
+            station: station.clone(),
+            probe: probe.clone(),
+        }
+    }
+}
+
+/// A position sentence of the receiver, other sentences are ignored
+#[derive(Clone, Debug, PartialEq)]
+enum Sentence {
+    /// GGA, the fix with its altitude, `None` without a fix
+    Gga {
+        time: String,
+        position: Option<(f64, f64)>,
+        satellites: Option<u32>,
+        hdop: Option<f64>,
+        altitude: Option<f64>,
+    },
+    /// RMC, the fix without altitude, `None` without a valid fix
+    Rmc {
+        time: String,
+        position: Option<(f64, f64)>,
+    },
+}
+
+/// Converts an NMEA coordinate, e.g. `4807.038` and `N`, to decimal degrees
+fn parse_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let value: f64 = value.parse().ok()?;
+    let degrees = (value / 100.0).trunc();
+    let decimal = degrees + (value - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(decimal),
+        "S" | "W" => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// Parses a GGA or RMC sentence from any talker, e.g. `$GPGGA` or `$GNRMC`
+fn parse_sentence(line: &str) -> Result<Option<Sentence>, String> {
+    let line = line
+        .trim()
+        .strip_prefix('$')
+        .ok_or("The sentence does not start with $")?;
+    let payload =
+        checksum::verify(line, ChecksumKind::Nmea, true).map_err(|e| e.to_string())?;
+    let fields: Vec<&str> = payload.split(',').collect();
+    let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+    let position = |latitude: usize| {
+        Some((
+            parse_coordinate(field(latitude), field(latitude + 1))?,
+            parse_coordinate(field(latitude + 2), field(latitude + 3))?,
+        ))
+    };
+    let sentence = match field(0).get(2..) {
+        Some("GGA") => {
+            // A fix quality of 0 means no fix
+            let fix = field(6).parse::<u8>().is_ok_and(|quality| quality > 0);
+            Sentence::Gga {
+                time: field(1).to_string(),
+                position: position(2).filter(|_| fix),
+                satellites: field(7).parse().ok(),
+                hdop: field(8).parse().ok(),
+                altitude: field(9).parse().ok(),
+            }
+        }
+        Some("RMC") => Sentence::Rmc {
+            time: field(1).to_string(),
+            position: position(3).filter(|_| field(2) == "A"),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(sentence))
+}
+
+#[derive(Default)]
+struct GroundGps {
+    position: Option<GroundPosition>,
+    probe: Option<ProbePosition>,
+}
+
+impl GroundGps {
+    /// Applies a sentence and returns the new position of the station, `None` if the
+    /// sentence has no fix. RMC sentences only move a position a GGA sentence gave an
+    /// altitude to
+    fn update(&mut self, sentence: Sentence) -> Option<GroundPosition> {
+        match sentence {
+            Sentence::Gga {
+                time,
+                position: Some((latitude, longitude)),
+                satellites,
+                hdop,
+                altitude: Some(altitude),
+            } => {
+                self.position = Some(GroundPosition {
+                    latitude,
+                    longitude,
+                    altitude,
+                    satellites,
+                    hdop,
+                    fix_time: time,
+                    received_utc: Utc::now(),
+                });
+            }
+            Sentence::Rmc {
+                time,
+                position: Some((latitude, longitude)),
+            } => {
+                let position = self.position.as_mut()?;
+                position.latitude = latitude;
+                position.longitude = longitude;
+                position.fix_time = time;
+                position.received_utc = Utc::now();
+            }
+            _ => return None,
+        }
+        self.position.clone()
+    }
+
+    fn pointing(&self) -> Option<AntennaPointing> {
+        Some(AntennaPointing::new(
+            self.position.as_ref()?,
+            self.probe.as_ref()?,
+            Utc::now(),
+        ))
+    }
+}
+
+/// Takes the position of a received packet and returns where to point the antenna,
+/// `None` without a fix of the probe or of the station
+pub async fn track_probe(telemetry: &Telemetry) -> Option<AntennaPointing> {
+    if !telemetry.has_gps_fix() {
+        return None;
+    }
+    let mut gps = GROUND_GPS.lock().await;
+    gps.probe = Some(ProbePosition {
+        packet_count: telemetry.packet_count,
+        latitude: telemetry.gps_latitude as f64,
+        longitude: telemetry.gps_longitude as f64,
+        altitude: telemetry.gps_altitude as f64,
+        received_utc: telemetry.received_utc,
+    });
+    gps.pointing()
+}
+
+/// Connects the NMEA GPS receiver of the ground station, its position is sent as
+/// `ground-position` events and, once the probe reported a fix, the antenna pointing
+/// as `antenna-pointing` events. The receiver is listed with the other connections
+#[tauri::command(rename_all = "snake_case")]
+pub async fn start_ground_gps(
+    app_handle: AppHandle,
+    device: String,
+    baudrate: u32,
+    connection_id: Option<String>,
+) -> Result<(), String> {
+    let connection_id = connection_id.unwrap_or_else(|| device.clone());
+    connections::check_available(&connection_id, &device).await?;
+    println!("Connecting to the GPS receiver on {}", device);
+
+    let builder =
+        SerialSettings::default().configure(tokio_serial::new(&device, baudrate));
+    let serial_stream = builder
+        .open_native_async()
+        .map_err(|e| format!("Error connecting to the GPS receiver: {}", e))?;
+    let (read_port, write_port) = split(serial_stream);
+    let registration = connections::register(
+        &connection_id,
+        ConnectionKind::GroundGps,
+        &device,
+        baudrate as i32,
+        Framing::Transparent,
+        write_port,
+    )
+    .await?;
+    let mut stopped = registration.stopped;
+    GROUND_GPS.lock().await.position = None;
+    connections::emit_changed(&app_handle).await;
+
+    tokio::spawn(async move {
+        let mut lines =
+            FramedRead::new(read_port, TelemetryLineCodec::new(MAX_LINE_LENGTH));
+        loop {
+            let frame = tokio::select! {
+                frame = lines.next() => frame,
+                _ = &mut stopped => {
+                    println!("Closing connection {}", connection_id);
+                    break;
+                }
+            };
+            let line = match frame {
+                Some(Ok(Frame::Line(line))) => line,
+                Some(Ok(frame)) => {
+                    eprintln!("Dropped a line of the GPS receiver: {:?}", frame);
+                    connections::count_packet(&connection_id, false).await;
+                    continue;
+                }
+                Some(Err(e)) => {
+                    eprintln!("Failed to read from the GPS receiver: {}", e);
+                    continue;
+                }
+                None => {
+                    println!("The serial port of {} was closed", connection_id);
+                    break;
+                }
+            };
+            let sentence = match parse_sentence(&line) {
+                Ok(Some(sentence)) => sentence,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("Rejected a sentence, {}: {:?}", e, line);
+                    connections::count_packet(&connection_id, false).await;
+                    continue;
+                }
+            };
+            connections::count_packet(&connection_id, true).await;
+
+            let (position, pointing) = {
+                let mut gps = GROUND_GPS.lock().await;
+                match gps.update(sentence) {
+                    Some(position) => (position, gps.pointing()),
+                    None => continue,
+                }
+            };
+            app_handle
+                .emit_all("ground-position", position)
+                .expect("failed to emit event");
+            if let Some(pointing) = pointing {
+                app_handle
+                    .emit_all("antenna-pointing", pointing)
+                    .expect("failed to emit event");
+            }
+        }
+        // A position from a receiver that is gone would point the antenna from
+        // wherever the station was last
+        GROUND_GPS.lock().await.position = None;
+        connections::remove(&connection_id).await;
+        connections::emit_changed(&app_handle).await;
+    });
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_ground_position() -> Option<GroundPosition> {
+    GROUND_GPS.lock().await.position.clone()
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_antenna_pointing() -> Option<AntennaPointing> {
+    GROUND_GPS.lock().await.pointing()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &str =
+        "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+    const RMC: &str =
+        "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn station(latitude: f64, longitude: f64, altitude: f64) -> GroundPosition {
+        GroundPosition {
+            latitude,
+            longitude,
+            altitude,
+            satellites: None,
+            hdop: None,
+            fix_time: String::new(),
+            received_utc: Utc::now(),
+        }
+    }
+
+    fn probe(latitude: f64, longitude: f64, altitude: f64) -> ProbePosition {
+        ProbePosition {
+            packet_count: 1,
+            latitude,
+            longitude,
+            altitude,
+            received_utc: None,
+        }
+    }
+
+    #[test]
+    fn converts_coordinates_to_decimal_degrees() {
+        assert_close(parse_coordinate("4807.038", "N").unwrap(), 48.1173, 1e-9);
+        assert_close(
+            parse_coordinate("01131.000", "E").unwrap(),
+            11.516_667,
+            1e-6,
+        );
+        assert_close(parse_coordinate("4807.038", "S").unwrap(), -48.1173, 1e-9);
+        assert_close(parse_coordinate("08033.900", "W").unwrap(), -80.565, 1e-9);
+        assert_eq!(parse_coordinate("4807.038", ""), None);
+        assert_eq!(parse_coordinate("", "N"), None);
+    }
+
+    #[test]
+    fn parses_the_reference_gga_sentence() {
+        match parse_sentence(GGA) {
+            Ok(Some(Sentence::Gga {
+                time,
+                position: Some((latitude, longitude)),
+                satellites,
+                hdop,
+                altitude,
+            })) => {
+                assert_eq!(time, "123519");
+                assert_close(latitude, 48.1173, 1e-9);
+                assert_close(longitude, 11.516_667, 1e-6);
+                assert_eq!(satellites, Some(8));
+                assert_eq!(hdop, Some(0.9));
+                assert_eq!(altitude, Some(545.4));
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_rmc_and_ignores_other_sentences() {
+        match parse_sentence(RMC) {
+            Ok(Some(Sentence::Rmc {
+                time,
+                position: Some((latitude, _)),
+            })) => {
+                assert_eq!(time, "123519");
+                assert_close(latitude, 48.1173, 1e-9);
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert_eq!(parse_sentence("$GPGSV,1,1,00*79"), Ok(None));
+        // A fix quality of 0 has no position
+        assert!(matches!(
+            parse_sentence("$GPGGA,123519,,,,,0,00,,,M,,M,,*6B"),
+            Ok(Some(Sentence::Gga { position: None, .. }))
+        ));
+    }
+
+    #[test]
+    fn rejects_damaged_sentences() {
+        assert!(parse_sentence(&GGA.replace("*47", "*48")).is_err());
+        assert!(parse_sentence(&GGA[1..]).is_err());
+    }
+
+    #[test]
+    fn waits_for_a_gga_altitude_before_rmc_positions() {
+        let mut gps = GroundGps::default();
+        let rmc = parse_sentence(RMC).unwrap().unwrap();
+        assert!(gps.update(rmc.clone()).is_none());
+        let position = gps.update(parse_sentence(GGA).unwrap().unwrap()).unwrap();
+        assert_eq!(position.altitude, 545.4);
+        assert_eq!(gps.update(rmc).unwrap().altitude, 545.4);
+    }
+
+    #[test]
+    fn points_the_antenna_at_the_probe() {
+        // 0.01 degrees north of the station and 1000 m above it
+        let now = Utc::now();
+        let pointing = AntennaPointing::new(
+            &station(48.0, 11.0, 500.0),
+            &probe(48.01, 11.0, 1500.0),
+            now,
+        );
+        assert_close(pointing.ground_distance_m, 1111.949, 1e-3);
+        assert_close(pointing.bearing_deg, 0.0, 1e-9);
+        assert_close(pointing.elevation_deg, 41.962_966, 1e-6);
+        assert_close(pointing.range_m, 1495.405, 1e-3);
+
+        // On the horizon, the curvature of the Earth puts the probe slightly below it
+        let pointing =
+            AntennaPointing::new(&station(0.0, 0.0, 0.0), &probe(0.0, 0.01, 0.0), now);
+        assert_close(pointing.bearing_deg, 90.0, 1e-9);
+        assert_close(pointing.elevation_deg, -0.005, 1e-6);
+
+        let pointing = AntennaPointing::new(
+            &station(0.0, 0.0, 0.0),
+            &probe(-0.01, -0.01, 0.0),
+            now,
+        );
+        assert_close(pointing.bearing_deg, 225.0, 1e-3);
+    }
+
+    #[test]
+    fn reports_the_age_of_both_fixes() {
+        let now = Utc::now();
+        let station = GroundPosition {
+            received_utc: now - chrono::Duration::milliseconds(2500),
+            ..station(48.0, 11.0, 500.0)
+        };
+        let pointing = AntennaPointing::new(&station, &probe(48.01, 11.0, 1500.0), now);
+        assert_close(pointing.station_fix_age_s, 2.5, 1e-9);
+        assert_eq!(pointing.probe_fix_age_s, None);
+
+        let probe = ProbePosition {
+            received_utc: Some(now - chrono::Duration::seconds(4)),
+            ..probe(48.01, 11.0, 1500.0)
+        };
+        let pointing = AntennaPointing::new(&station, &probe, now);
+        assert_eq!(pointing.probe_fix_age_s, Some(4.0));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::report::mission_seconds;
+use crate::telemetry_schema::TELEMETRY_SCHEMA;
+use crate::units::DISPLAY_UNITS;
+use crate::{Telemetry, TELEMETRY};
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+lazy_static! {
+    pub static ref HISTORY_CONFIG: Arc<tokio::sync::Mutex<HistoryConfig>> =
+        Arc::new(tokio::sync::Mutex::new(HistoryConfig::default()));
+    /// Whether the retention dropped packets of the telemetry in memory
+    static ref HISTORY_TRUNCATED: Arc<tokio::sync::Mutex<bool>> =
+        Arc::new(tokio::sync::Mutex::new(false));
+    /// Session telemetry log the packets in memory were written to, it outlives the
+    /// session so a flight can still be exported after the radios disconnected
+    static ref FLIGHT_LOG: Arc<tokio::sync::Mutex<Option<PathBuf>>> =
+        Arc::new(tokio::sync::Mutex::new(None));
+}
+
+/// How much telemetry is kept in memory. Everything is still written to the session
+/// directory and the database, this only bounds what the UI can plot without a query
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Most packets kept in memory, the oldest are dropped first. Also bounds the sent
+    /// commands and alarms
+    pub max_packets: usize,
+    /// Packets received longer ago than this many seconds are dropped. Packets
+    /// loaded from files have no receive time and are only bounded by `max_packets`
+    pub max_age_s: Option<u64>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        // A full day at the 1 Hz packet rate
+        HistoryConfig {
+            max_packets: 86_400,
+            max_age_s: None,
+        }
+    }
+}
+
+/// A value of a field at a point in time, time in seconds of MISSION_TIME
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeriesSample {
+    pub time_s: f64,
+    pub value: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DownsampledSeries {
+    pub field: String,
+    /// Unit of the values, in the display units
+    pub unit: Option<String>,
+    pub from_s: f64,
+    pub to_s: f64,
+    /// Packets inside the window before downsampling
+    pub packets_in_window: usize,
+    pub samples: Vec<SeriesSample>,
+}
+
+/// Drops the packets outside the retention, returns whether any were dropped
+fn enforce_retention(
+    telemetry: &mut VecDeque<Telemetry>,
+    config: &HistoryConfig,
+) -> bool {
+    let before = telemetry.len();
+    while telemetry.len() > config.max_packets {
+        telemetry.pop_front();
+    }
+    if let Some(max_age_s) = config.max_age_s {
+        let cutoff = Utc::now() - Duration::seconds(max_age_s as i64);
+        while telemetry
+            .front()
+            .and_then(|t| t.received_utc)
+            .is_some_and(|received| received < cutoff)
+        {
+            telemetry.pop_front();
+        }
+    }
+    telemetry.len() < before
+}
+
+/// Adds a received packet, dropping whatever falls out of the retention
+pub async fn push(packet: Telemetry) {
+    let config = HISTORY_CONFIG.lock().await.clone();
+    let mut telemetry = TELEMETRY.lock().await;
+    telemetry.push_back(packet);
+    if enforce_retention(&mut telemetry, &config) {
+        *HISTORY_TRUNCATED.lock().await = true;
+    }
+}
+
+/// Replaces the whole history, e.g. with a recovered or loaded flight. Returns the
+/// packets that were kept. The telemetry log stays as it is, `set_flight_log` changes
+/// it when the packets come from elsewhere
+pub async fn replace(packets: Vec<Telemetry>) -> Vec<Telemetry> {
+    let config = HISTORY_CONFIG.lock().await.clone();
+    let mut telemetry = TELEMETRY.lock().await;
+    *telemetry = VecDeque::from(packets);
+    *HISTORY_TRUNCATED.lock().await = enforce_retention(&mut telemetry, &config);
+    telemetry.iter().cloned().collect()
+}
+
+/// Sets the session telemetry log the packets in memory are written to, `None` if they
+/// are not in any
+pub async fn set_flight_log(path: Option<PathBuf>) {
+    *FLIGHT_LOG.lock().await = path;
+}
+
+/// Empties the history when a new session starts, the packets of the previous one stay
+/// in its telemetry log
+pub async fn clear() {
+    TELEMETRY.lock().await.clear();
+    *HISTORY_TRUNCATED.lock().await = false;
+    *FLIGHT_LOG.lock().await = None;
+}
+
+/// Copy of the telemetry in memory, oldest first
+pub async fn snapshot() -> Vec<Telemetry> {
+    TELEMETRY.lock().await.iter().cloned().collect()
+}
+
+/// All telemetry of the flight for the CSV, the report and the exports. The memory
+/// holds it unless the retention dropped packets, then the session telemetry log is
+/// read back
+pub async fn whole_flight() -> Result<Vec<Telemetry>, String> {
+    if !*HISTORY_TRUNCATED.lock().await {
+        return Ok(snapshot().await);
+    }
+    let flight_log = FLIGHT_LOG.lock().await.clone();
+    match flight_log {
+        Some(path) if path.exists() => {
+            let (telemetry, skipped) = TELEMETRY_SCHEMA.lock().await.read_csv(&path)?;
+            if skipped > 0 {
+                println!("Skipped {} damaged rows of the telemetry log", skipped);
+            }
+            Ok(telemetry)
+        }
+        _ => {
+            eprintln!(
+                "No session log to read the dropped packets from, exporting the rest"
+            );
+            Ok(snapshot().await)
+        }
+    }
+}
+
+fn field_accessor(field: &str) -> Result<fn(&Telemetry) -> f64, String> {
+    let accessor: fn(&Telemetry) -> f64 = match field {
+        "packet_count" => |t| t.packet_count as f64,
+        "altitude" => |t| t.altitude as f64,
+        "temperature" => |t| t.temperature as f64,
+        "pressure" => |t| t.pressure as f64,
+        "voltage" => |t| t.voltage as f64,
+        "gps_altitude" => |t| t.gps_altitude as f64,
+        "gps_latitude" => |t| t.gps_latitude as f64,
+        "gps_longitude" => |t| t.gps_longitude as f64,
+        "gps_sats" => |t| t.gps_sats as f64,
+        "tilt_x" => |t| t.tilt_x as f64,
+        "tilt_y" => |t| t.tilt_y as f64,
+        _ => return Err(format!("Unknown telemetry field: {}", field)),
+    };
+    Ok(accessor)
+}
+
+/// Reduces the samples to at most `max_points` by splitting the window into buckets
+/// and keeping the lowest and highest sample of each, so spikes survive zooming out
+fn downsample_min_max(
+    samples: Vec<SeriesSample>,
+    from_s: f64,
+    to_s: f64,
+    max_points: usize,
+) -> Vec<SeriesSample> {
+    if samples.len() <= max_points {
+        return samples;
+    }
+    let buckets = (max_points / 2).max(1);
+    let width = (to_s - from_s) / buckets as f64;
+
+    let mut downsampled = Vec::with_capacity(buckets * 2);
+    let mut current: Option<(usize, usize, usize)> = None;
+    let flush = |downsampled: &mut Vec<SeriesSample>,
+                 (_, min, max): (usize, usize, usize)| {
+        // Keep the two samples in time order
+        downsampled.push(samples[min.min(max)].clone());
+        if min != max {
+            downsampled.push(samples[min.max(max)].clone());
+        }
+    };
+    for (index, sample) in samples.iter().enumerate() {
+        let bucket = if width > 0.0 {
+            (((sample.time_s - from_s) / width) as usize).min(buckets - 1)
+        } else {
+            0
+        };
+        current = match current {
+            Some((current_bucket, mut min, mut max)) if current_bucket == bucket => {
+                if sample.value < samples[min].value {
+                    min = index;
+                }
+                if sample.value > samples[max].value {
+                    max = index;
+                }
+                Some((bucket, min, max))
+            }
+            previous => {
+                if let Some(previous) = previous {
+                    flush(&mut downsampled, previous);
+                }
+                Some((bucket, index, index))
+            }
+        };
+    }
+    if let Some(last) = current {
+        flush(&mut downsampled, last);
+    }
+    downsampled
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_history_config(config: HistoryConfig) -> Result<(), String> {
+    if config.max_packets == 0 {
+        return Err("The history must keep at least one packet.".to_string());
+    }
+    println!("New history config: {:?}", config);
+    *HISTORY_CONFIG.lock().await = config.clone();
+    if enforce_retention(&mut *TELEMETRY.lock().await, &config) {
+        *HISTORY_TRUNCATED.lock().await = true;
+    }
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_history_config() -> HistoryConfig {
+    HISTORY_CONFIG.lock().await.clone()
+}
+
+/// Returns a numeric field over a window of MISSION_TIME seconds, reduced to at most
+/// `max_points` samples while keeping the minimum and maximum of every stretch of
+/// time. Without `from_s` or `to_s` the window extends to the oldest or newest packet
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_downsampled_series(
+    field: String,
+    from_s: Option<f64>,
+    to_s: Option<f64>,
+    max_points: usize,
+) -> Result<DownsampledSeries, String> {
+    let accessor = field_accessor(&field)?;
+    if max_points < 2 {
+        return Err("At least two points are needed for a series.".to_string());
+    }
+
+    let telemetry = TELEMETRY.lock().await;
+    let times = mission_seconds(telemetry.iter());
+    let samples: Vec<SeriesSample> = telemetry
+        .iter()
+        .zip(times)
+        .filter_map(|(t, time_s)| {
+            let time_s = time_s?;
+            let inside = from_s.is_none_or(|from_s| time_s >= from_s)
+                && to_s.is_none_or(|to_s| time_s <= to_s);
+            inside.then(|| SeriesSample {
+                time_s,
+                value: accessor(t),
+            })
+        })
+        .collect();
+    drop(telemetry);
+
+    let units = *DISPLAY_UNITS.lock().await;
+    let schema = TELEMETRY_SCHEMA.lock().await;
+    let conversion = schema.conversion(&field, &units);
+    let unit = match conversion {
+        Some(conversion) => Some(conversion.unit.to_string()),
+        None => schema
+            .fields
+            .iter()
+            .find(|f| f.name == field)
+            .and_then(|f| f.unit.clone()),
+    };
+    drop(schema);
+    let samples: Vec<SeriesSample> = match conversion {
+        Some(conversion) => samples
+            .into_iter()
+            .map(|sample| SeriesSample {
+                value: conversion.apply(sample.value),
+                ..sample
+            })
+            .collect(),
+        None => samples,
+    };
+
+    let from_s = from_s
+        .or_else(|| samples.first().map(|s| s.time_s))
+        .unwrap_or(0.0);
+    let to_s = to_s
+        .or_else(|| samples.last().map(|s| s.time_s))
+        .unwrap_or(from_s);
+    let packets_in_window = samples.len();
+    Ok(DownsampledSeries {
+        field,
+        unit,
+        from_s,
+        to_s,
+        packets_in_window,
+        samples: downsample_min_max(samples, from_s, to_s, max_points),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: &[f64]) -> Vec<SeriesSample> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| SeriesSample {
+                time_s: index as f64,
+                value: *value,
+            })
+            .collect()
+    }
+
+    fn values(samples: &[SeriesSample]) -> Vec<(f64, f64)> {
+        samples.iter().map(|s| (s.time_s, s.value)).collect()
+    }
+
+    #[test]
+    fn keeps_the_minimum_and_maximum_of_each_bucket_in_time_order() {
+        // Two buckets of four seconds over 0..8, with a spike in each
+        let series = samples(&[5.0, 9.0, 4.0, 6.0, 3.0, 1.0, 7.0, 2.0, 8.0]);
+        let downsampled = downsample_min_max(series, 0.0, 8.0, 4);
+        assert_eq!(
+            values(&downsampled),
+            vec![(1.0, 9.0), (2.0, 4.0), (5.0, 1.0), (8.0, 8.0)]
+        );
+    }
+
+    #[test]
+    fn keeps_a_single_sample_for_a_flat_bucket() {
+        let series = samples(&[2.0, 2.0, 2.0, 5.0, 1.0, 3.0]);
+        let downsampled = downsample_min_max(series, 0.0, 6.0, 4);
+        assert_eq!(
+            values(&downsampled),
+            vec![(0.0, 2.0), (3.0, 5.0), (4.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn leaves_short_series_alone() {
+        let series = samples(&[1.0, 2.0, 3.0]);
+        assert_eq!(values(&downsample_min_max(series, 0.0, 2.0, 4)).len(), 3);
+    }
+}
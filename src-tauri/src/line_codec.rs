@@ -0,0 +1,317 @@
+use bytes::BytesMut;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_util::codec::Decoder;
+
+/// Longest line accepted from the device, a telemetry packet is about 150 bytes
+pub const MAX_LINE_LENGTH: usize = 512;
+
+lazy_static! {
+    pub static ref FRAMING_STATS: Arc<tokio::sync::Mutex<FramingStats>> =
+        Arc::new(tokio::sync::Mutex::new(FramingStats::default()));
+}
+
+/// Counters of the line framing of the current connection
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FramingStats {
+    /// Complete lines that were valid UTF-8
+    pub lines: u64,
+    /// Lines longer than `MAX_LINE_LENGTH`, discarded up to the next line ending
+    pub overflows: u64,
+    /// Bytes thrown away by overflows
+    pub discarded_bytes: u64,
+    /// Lines that were not valid UTF-8
+    pub invalid_utf8: u64,
+}
+
+impl FramingStats {
+    pub fn record(&mut self, frame: &Frame) {
+        match frame {
+            Frame::Line(_) => self.lines += 1,
+            Frame::InvalidUtf8(_) => self.invalid_utf8 += 1,
+            Frame::Overflow { discarded } => {
+                self.overflows += 1;
+                self.discarded_bytes += *discarded as u64;
+            }
+        }
+    }
+}
+
+/// A line read from the device, without its line ending
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Frame {
+    Line(String),
+    /// The line was not valid UTF-8, carries the line with the invalid bytes replaced
+    InvalidUtf8(String),
+    /// A line exceeded the maximum length and was dropped
+    Overflow {
+        discarded: usize,
+    },
+}
+
+/// Splits the serial stream into lines terminated by LF or CRLF. Lines longer than
+/// the maximum are dropped up to the next line ending, so a noisy link resyncs on
+/// the next packet instead of growing the buffer forever
+pub struct TelemetryLineCodec {
+    max_length: usize,
+    /// Bytes of the buffer already searched for a line ending
+    searched: usize,
+    /// Bytes dropped so far while waiting for the end of an overlong line
+    discarding: Option<usize>,
+    /// Set whenever bytes arrive, see `take_activity`
+    activity: bool,
+}
+
+impl TelemetryLineCodec {
+    pub fn new(max_length: usize) -> Self {
+        TelemetryLineCodec {
+            max_length,
+            searched: 0,
+            discarding: None,
+            activity: false,
+        }
+    }
+
+    /// Whether any bytes arrived since the last call, also for lines that are not
+    /// complete yet
+    pub fn take_activity(&mut self) -> bool {
+        std::mem::take(&mut self.activity)
+    }
+
+    /// The bytes of a line without its LF or CRLF ending, also for a line whose LF
+    /// has not arrived yet
+    fn content(bytes: &[u8]) -> &[u8] {
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        bytes.strip_suffix(b"\r").unwrap_or(bytes)
+    }
+
+    fn line(bytes: &[u8]) -> Frame {
+        let bytes = Self::content(bytes);
+        match std::str::from_utf8(bytes) {
+            Ok(line) => Frame::Line(line.to_string()),
+            Err(_) => Frame::InvalidUtf8(String::from_utf8_lossy(bytes).to_string()),
+        }
+    }
+}
+
+impl Decoder for TelemetryLineCodec {
+    type Item = Frame;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, std::io::Error> {
+        if !buf.is_empty() {
+            self.activity = true;
+        }
+        loop {
+            let newline = buf[self.searched..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map(|offset| self.searched + offset);
+
+            if let Some(discarded) = self.discarding {
+                return Ok(match newline {
+                    Some(newline) => {
+                        let _ = buf.split_to(newline + 1);
+                        self.searched = 0;
+                        self.discarding = None;
+                        Some(Frame::Overflow {
+                            discarded: discarded + newline + 1,
+                        })
+                    }
+                    None => {
+                        self.discarding = Some(discarded + buf.len());
+                        buf.clear();
+                        self.searched = 0;
+                        None
+                    }
+                });
+            }
+
+            match newline {
+                Some(newline) => {
+                    let line = buf.split_to(newline + 1);
+                    self.searched = 0;
+                    if Self::content(&line).len() > self.max_length {
+                        return Ok(Some(Frame::Overflow {
+                            discarded: line.len(),
+                        }));
+                    }
+                    if line.iter().all(|byte| *byte == b'\r' || *byte == b'\n') {
+                        // Blank lines carry nothing, not even an error
+                        continue;
+                    }
+                    return Ok(Some(Self::line(&line)));
+                }
+                None if Self::content(buf).len() > self.max_length => {
+                    self.discarding = Some(buf.len());
+                    buf.clear();
+                    self.searched = 0;
+                    return Ok(None);
+                }
+                None => {
+                    self.searched = buf.len();
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn decode_eof(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<Frame>, std::io::Error> {
+        if let Some(frame) = self.decode(buf)? {
+            return Ok(Some(frame));
+        }
+        self.searched = 0;
+        if let Some(discarded) = self.discarding.take() {
+            return Ok(Some(Frame::Overflow { discarded }));
+        }
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        let line = buf.split_to(buf.len());
+        Ok(Some(Self::line(&line)))
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_framing_stats() -> FramingStats {
+    FRAMING_STATS.lock().await.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut TelemetryLineCodec, buf: &mut BytesMut) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = codec.decode(buf).unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn line(text: &str) -> Frame {
+        Frame::Line(text.to_string())
+    }
+
+    #[test]
+    fn splits_lf_and_crlf_lines_and_skips_blank_ones() {
+        let mut codec = TelemetryLineCodec::new(MAX_LINE_LENGTH);
+        let mut buf = BytesMut::from(&b"1082,A\r\n\r\n1082,B\n\n1082,C\r\n"[..]);
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            vec![line("1082,A"), line("1082,B"), line("1082,C")]
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn joins_a_line_split_across_reads() {
+        let mut codec = TelemetryLineCodec::new(MAX_LINE_LENGTH);
+        let mut buf = BytesMut::from(&b"1082,13:1"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(codec.take_activity());
+        buf.extend_from_slice(b"4:02,1\r");
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\n1082");
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(line("1082,13:14:02,1"))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert_eq!(&buf[..], b"1082");
+    }
+
+    #[test]
+    fn resyncs_on_the_next_line_after_an_overflow() {
+        let mut codec = TelemetryLineCodec::new(MAX_LINE_LENGTH);
+        let mut buf = BytesMut::from(&[b'x'; 600][..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b"tail\n1082,A\n");
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            vec![Frame::Overflow { discarded: 605 }, line("1082,A")]
+        );
+    }
+
+    #[test]
+    fn drops_a_complete_line_longer_than_the_maximum() {
+        let mut codec = TelemetryLineCodec::new(8);
+        // The maximum does not count the line ending
+        let mut buf = BytesMut::from(&b"12345678\r\n1234567890\n"[..]);
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            vec![line("12345678"), Frame::Overflow { discarded: 11 }]
+        );
+    }
+
+    #[test]
+    fn accepts_a_line_of_the_maximum_length_only() {
+        let mut codec = TelemetryLineCodec::new(8);
+        let mut buf =
+            BytesMut::from(&b"12345678\n123456789\n123456789\r\n12345678\r\n"[..]);
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            vec![
+                line("12345678"),
+                Frame::Overflow { discarded: 10 },
+                Frame::Overflow { discarded: 11 },
+                line("12345678"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_a_maximum_length_crlf_line_split_before_the_lf() {
+        let mut codec = TelemetryLineCodec::new(8);
+        let mut buf = BytesMut::from(&b"12345678\r"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert_eq!(&buf[..], b"12345678\r");
+        buf.extend_from_slice(b"\n");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(line("12345678")));
+
+        // One byte more is an overflow even before the line ending arrives
+        let mut buf = BytesMut::from(&b"123456789"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b"\r\n");
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::Overflow { discarded: 11 })
+        );
+    }
+
+    #[test]
+    fn reports_invalid_utf8_with_replacement_characters() {
+        let mut codec = TelemetryLineCodec::new(MAX_LINE_LENGTH);
+        let mut buf = BytesMut::from(&b"1082,\xff\xfe\r\n"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Frame::InvalidUtf8("1082,\u{fffd}\u{fffd}".to_string()))
+        );
+    }
+
+    #[test]
+    fn returns_the_unterminated_rest_at_eof() {
+        let mut codec = TelemetryLineCodec::new(MAX_LINE_LENGTH);
+        let mut buf = BytesMut::from(&b"1082,A\n1082,B"[..]);
+        assert_eq!(codec.decode_eof(&mut buf).unwrap(), Some(line("1082,A")));
+        assert_eq!(codec.decode_eof(&mut buf).unwrap(), Some(line("1082,B")));
+        assert_eq!(codec.decode_eof(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn reports_an_overflow_cut_short_by_eof() {
+        let mut codec = TelemetryLineCodec::new(MAX_LINE_LENGTH);
+        let mut buf = BytesMut::from(&[b'x'; 600][..]);
+        assert_eq!(
+            codec.decode_eof(&mut buf).unwrap(),
+            Some(Frame::Overflow { discarded: 600 })
+        );
+        assert_eq!(codec.decode_eof(&mut buf).unwrap(), None);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::connections;
+use crate::ground_gps::AntennaPointing;
+use crate::history::HISTORY_CONFIG;
+use crate::report::haversine_m;
+use crate::xbee::TransmitStatus;
+use crate::Telemetry;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+lazy_static! {
+    pub static ref LINK_QUALITY_CONFIG: Arc<tokio::sync::Mutex<LinkQualityConfig>> =
+        Arc::new(tokio::sync::Mutex::new(LinkQualityConfig::default()));
+    /// Link quality of every connection by its ID
+    static ref LINK_QUALITY: Arc<tokio::sync::Mutex<BTreeMap<String, LinkQuality>>> =
+        Arc::new(tokio::sync::Mutex::new(BTreeMap::new()));
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkQualityConfig {
+    /// Packets over which the packet loss is computed
+    pub loss_window_packets: usize,
+    /// How often in milliseconds the local radio is asked for the signal strength
+    /// with the DB command, for firmware that does not report it in the RX frames.
+    /// Needs API framing, only applied when a new connection is started
+    pub rssi_poll_interval_ms: Option<u64>,
+    /// Age in milliseconds after which a polled signal strength is no longer used
+    pub rssi_max_age_ms: u64,
+}
+
+impl Default for LinkQualityConfig {
+    fn default() -> Self {
+        LinkQualityConfig {
+            loss_window_packets: 20,
+            rssi_poll_interval_ms: None,
+            rssi_max_age_ms: 3000,
+        }
+    }
+}
+
+/// Link quality at the time of a received packet, payload of the `link-quality` event
+/// and a row of the session link quality log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkQualitySample {
+    /// The connection that received the packet, a packet received by several radios
+    /// gives a sample for each of them
+    pub connection_id: String,
+    pub received_utc: Option<DateTime<Utc>>,
+    pub received_monotonic_s: Option<f64>,
+    pub packet_count: i32,
+    /// Signal strength of the packet, or the last polled one
+    pub rssi_dbm: Option<i16>,
+    /// Fraction of the packets of the loss window that never arrived, judged by
+    /// PACKET_COUNT
+    pub packet_loss: f64,
+    /// Packets missing since the connection was opened
+    pub packets_lost: u64,
+    /// Retransmissions of commands reported since the previous sample
+    pub retries: u64,
+    /// Commands that were not delivered since the previous sample
+    pub failed_transmissions: u64,
+    /// Straight line distance in meters to the ground station when its GPS gives its
+    /// position, otherwise to the first GPS fix of the session, near the launch site
+    pub distance_m: Option<f64>,
+    /// Whether `distance_m` is measured from the ground station
+    pub distance_from_station: bool,
+    /// Direction of the probe from the ground station, see `AntennaPointing`
+    pub bearing_deg: Option<f64>,
+    pub elevation_deg: Option<f64>,
+    pub altitude: f32,
+    /// Orientation of the probe, and with it of its antenna
+    pub tilt_x: f32,
+    pub tilt_y: f32,
+}
+
+#[derive(Default)]
+struct LinkQuality {
+    last_packet_count: Option<i32>,
+    /// Packets missing before each packet of the loss window
+    window: VecDeque<u64>,
+    packets_lost: u64,
+    retries: u64,
+    failed_transmissions: u64,
+    polled_rssi: Option<(i16, DateTime<Utc>)>,
+    /// Latitude, longitude and altitude of the first GPS fix
+    reference: Option<(f64, f64, f64)>,
+    samples: VecDeque<LinkQualitySample>,
+}
+
+impl LinkQuality {
+    fn record(
+        &mut self,
+        connection_id: &str,
+        telemetry: &Telemetry,
+        pointing: Option<&AntennaPointing>,
+        config: &LinkQualityConfig,
+    ) -> LinkQualitySample {
+        // A lower count means the probe reset its counter, nothing was lost
+        let lost = match self.last_packet_count {
+            Some(last) if telemetry.packet_count > last => {
+                (telemetry.packet_count - last - 1) as u64
+            }
+            _ => 0,
+        };
+        self.last_packet_count = Some(telemetry.packet_count);
+        self.packets_lost += lost;
+        self.window.push_back(lost);
+        while self.window.len() > config.loss_window_packets.max(1) {
+            self.window.pop_front();
+        }
+        let lost_in_window: u64 = self.window.iter().sum();
+        let expected = lost_in_window + self.window.len() as u64;
+
+        let polled_rssi = self.polled_rssi.and_then(|(rssi, time)| {
+            let age_ms = (Utc::now() - time).num_milliseconds();
+            (age_ms <= config.rssi_max_age_ms as i64).then_some(rssi)
+        });
+
+        let distance_m = if telemetry.has_gps_fix() {
+            let position = (
+                telemetry.gps_latitude as f64,
+                telemetry.gps_longitude as f64,
+                telemetry.gps_altitude as f64,
+            );
+            let (lat, lon, altitude) = *self.reference.get_or_insert(position);
+            let horizontal = haversine_m(lat, lon, position.0, position.1);
+            Some(horizontal.hypot(position.2 - altitude))
+        } else {
+            None
+        };
+
+        LinkQualitySample {
+            connection_id: connection_id.to_string(),
+            received_utc: telemetry.received_utc,
+            received_monotonic_s: telemetry.received_monotonic_s,
+            packet_count: telemetry.packet_count,
+            rssi_dbm: telemetry.rssi_dbm.or(polled_rssi),
+            packet_loss: lost_in_window as f64 / expected as f64,
+            packets_lost: self.packets_lost,
+            retries: std::mem::take(&mut self.retries),
+            failed_transmissions: std::mem::take(&mut self.failed_transmissions),
+            distance_m: pointing.map(|p| p.range_m).or(distance_m),
+            distance_from_station: pointing.is_some(),
+            bearing_deg: pointing.map(|p| p.bearing_deg),
+            elevation_deg: pointing.map(|p| p.elevation_deg),
+            altitude: telemetry.altitude,
+            tilt_x: telemetry.tilt_x,
+            tilt_y: telemetry.tilt_y,
+        }
+    }
+}
+
+/// Starts the link quality of a new connection, with `new_session` the one of the
+/// other connections is dropped too
+pub async fn reset(connection_id: &str, new_session: bool) {
+    let mut quality = LINK_QUALITY.lock().await;
+    if new_session {
+        quality.clear();
+    }
+    quality.insert(connection_id.to_string(), LinkQuality::default());
+}
+
+/// Adds a packet received on a connection and returns the link quality at its time,
+/// `pointing` is the direction of the packet from the ground station if its position
+/// is known
+pub async fn record_packet(
+    connection_id: &str,
+    telemetry: &Telemetry,
+    pointing: Option<&AntennaPointing>,
+) -> LinkQualitySample {
+    let config = LINK_QUALITY_CONFIG.lock().await.clone();
+    let max_samples = HISTORY_CONFIG.lock().await.max_packets;
+    let mut quality = LINK_QUALITY.lock().await;
+    let quality = quality.entry(connection_id.to_string()).or_default();
+    let sample = quality.record(connection_id, telemetry, pointing, &config);
+    quality.samples.push_back(sample.clone());
+    while quality.samples.len() > max_samples {
+        quality.samples.pop_front();
+    }
+    sample
+}
+
+pub async fn record_transmit_status(connection_id: &str, status: &TransmitStatus) {
+    let mut quality = LINK_QUALITY.lock().await;
+    let quality = quality.entry(connection_id.to_string()).or_default();
+    quality.retries += status.retries.unwrap_or(0) as u64;
+    if !status.delivered {
+        quality.failed_transmissions += 1;
+    }
+}
+
+/// Keeps the signal strength reported by the DB command for the next packets of the
+/// connection
+pub async fn record_polled_rssi(connection_id: &str, rssi_dbm: i16) {
+    LINK_QUALITY
+        .lock()
+        .await
+        .entry(connection_id.to_string())
+        .or_default()
+        .polled_rssi = Some((rssi_dbm, Utc::now()));
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_link_quality_config(config: LinkQualityConfig) -> Result<(), String> {
+    if config.loss_window_packets == 0 {
+        return Err("The loss window needs at least one packet.".to_string());
+    }
+    if config
+        .rssi_poll_interval_ms
+        .is_some_and(|interval| interval < 100)
+    {
+        return Err("The RSSI poll interval must be at least 100 ms.".to_string());
+    }
+    println!("New link quality config: {:?}", config);
+    *LINK_QUALITY_CONFIG.lock().await = config;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_link_quality_config() -> LinkQualityConfig {
+    LINK_QUALITY_CONFIG.lock().await.clone()
+}
+
+/// The link quality samples of a connection, by default the primary one, oldest
+/// first
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_link_quality_series(
+    connection_id: Option<String>,
+) -> Vec<LinkQualitySample> {
+    let connection_id = match connection_id {
+        Some(connection_id) => Some(connection_id),
+        None => connections::primary_id().await,
+    };
+    let quality = LINK_QUALITY.lock().await;
+    match connection_id.and_then(|connection_id| quality.get(&connection_id)) {
+        Some(quality) => quality.samples.iter().cloned().collect(),
+        None => Vec::new(),
+    }
+}
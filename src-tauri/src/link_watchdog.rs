@@ -0,0 +1,150 @@
+use crate::connections;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    pub static ref LINK_WATCHDOG_CONFIG: Arc<tokio::sync::Mutex<LinkWatchdogConfig>> =
+        Arc::new(tokio::sync::Mutex::new(LinkWatchdogConfig::default()));
+    /// Last status of every connection by its ID
+    pub static ref LINK_STATUS: Arc<tokio::sync::Mutex<BTreeMap<String, LinkStatusEvent>>> =
+        Arc::new(tokio::sync::Mutex::new(BTreeMap::new()));
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStatus {
+    /// Valid packets are arriving within the configured threshold
+    Healthy,
+    /// Bytes are arriving, but none of them formed a valid packet recently,
+    /// e.g. the probe is sending garbage or the radio link is noisy
+    Degraded,
+    /// Nothing at all has been received recently, the radio link is gone
+    Silent,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkWatchdogConfig {
+    /// Time in milliseconds without a valid packet after which the link is degraded
+    pub degraded_after_ms: u64,
+    /// Time in milliseconds without any raw byte after which the link is silent
+    pub silent_after_ms: u64,
+    /// How often in milliseconds the watchdog re-evaluates the link. Only applied
+    /// when a new connection is started
+    pub check_interval_ms: u64,
+}
+
+impl Default for LinkWatchdogConfig {
+    fn default() -> Self {
+        // The probe transmits at 1 Hz, so a few missed packets mean trouble
+        LinkWatchdogConfig {
+            degraded_after_ms: 3000,
+            silent_after_ms: 5000,
+            check_interval_ms: 500,
+        }
+    }
+}
+
+/// Payload of the `link-status` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkStatusEvent {
+    pub connection_id: String,
+    pub status: LinkStatus,
+    /// Milliseconds since the last valid packet, or since connecting if none arrived yet
+    pub since_last_packet_ms: u64,
+    /// Milliseconds since the last raw byte, or since connecting if none arrived yet
+    pub since_last_byte_ms: u64,
+}
+
+/// Tracks the activity of a single connection, owned by its read task
+pub struct LinkWatchdog {
+    connection_id: String,
+    connected_at: Instant,
+    last_byte: Option<Instant>,
+    last_packet: Option<Instant>,
+    status: Option<LinkStatus>,
+}
+
+impl LinkWatchdog {
+    pub fn new(connection_id: &str) -> Self {
+        LinkWatchdog {
+            connection_id: connection_id.to_string(),
+            connected_at: Instant::now(),
+            last_byte: None,
+            last_packet: None,
+            status: None,
+        }
+    }
+
+    pub fn byte_received(&mut self) {
+        self.last_byte = Some(Instant::now());
+    }
+
+    pub fn packet_received(&mut self) {
+        self.last_packet = Some(Instant::now());
+    }
+
+    /// Re-evaluates the link and returns an event only when the status has changed
+    pub fn check(&mut self, config: &LinkWatchdogConfig) -> Option<LinkStatusEvent> {
+        let event = self.evaluate(config);
+        if self.status == Some(event.status) {
+            return None;
+        }
+        self.status = Some(event.status);
+        Some(event)
+    }
+
+    fn evaluate(&self, config: &LinkWatchdogConfig) -> LinkStatusEvent {
+        let now = Instant::now();
+        let since_last_byte = now - self.last_byte.unwrap_or(self.connected_at);
+        let since_last_packet = now - self.last_packet.unwrap_or(self.connected_at);
+
+        let status = if since_last_byte >= Duration::from_millis(config.silent_after_ms)
+        {
+            LinkStatus::Silent
+        } else if since_last_packet >= Duration::from_millis(config.degraded_after_ms) {
+            LinkStatus::Degraded
+        } else {
+            LinkStatus::Healthy
+        };
+
+        LinkStatusEvent {
+            connection_id: self.connection_id.clone(),
+            status,
+            since_last_packet_ms: since_last_packet.as_millis() as u64,
+            since_last_byte_ms: since_last_byte.as_millis() as u64,
+        }
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_link_watchdog_config(
+    config: LinkWatchdogConfig,
+) -> Result<(), String> {
+    if config.degraded_after_ms == 0 || config.silent_after_ms == 0 {
+        return Err("Watchdog thresholds must be greater than zero.".to_string());
+    }
+    if config.check_interval_ms == 0 {
+        return Err("Watchdog check interval must be greater than zero.".to_string());
+    }
+    println!("New link watchdog config: {:?}", config);
+    *LINK_WATCHDOG_CONFIG.lock().await = config;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_link_watchdog_config() -> LinkWatchdogConfig {
+    LINK_WATCHDOG_CONFIG.lock().await.clone()
+}
+
+/// The link status of a connection, by default the primary one
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_link_status(connection_id: Option<String>) -> Option<LinkStatusEvent> {
+    let connection_id = match connection_id {
+        Some(connection_id) => connection_id,
+        None => connections::primary_id().await?,
+    };
+    LINK_STATUS.lock().await.get(&connection_id).cloned()
+}
@@ -4,24 +4,72 @@
 )]
 extern crate url;
 
-use core::panic;
+mod analysis_export;
+mod checksum;
+mod connections;
+mod emission;
+mod events;
+mod flight_compare;
+mod ground_gps;
+mod history;
+mod line_codec;
+mod link_quality;
+mod link_watchdog;
+mod packet_timing;
+mod packet_types;
+mod port_watcher;
+mod radio_setup;
+mod recovery;
+mod report;
+mod serial_config;
+mod session;
+mod store;
+mod telemetry_schema;
+mod time_sync;
+mod track_export;
+mod transport;
+mod units;
+mod xbee;
+
 use csv::WriterBuilder;
+use futures::StreamExt;
 use lazy_static::lazy_static;
 
 use serde::{Deserialize, Serialize};
-use serialport::available_ports;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs::OpenOptions;
 use std::io::Read;
+use std::path::Path;
 
-use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
 
-use std::{env, fs::File, sync::Arc};
+use std::{fs::File, sync::Arc};
 use tauri::http::{header::*, status::StatusCode, ResponseBuilder};
-use tauri::{AppHandle, Manager};
-use tokio::io::{split, AsyncReadExt, AsyncWriteExt, WriteHalf};
-use tokio_serial::{SerialPortBuilderExt, SerialStream};
+use tauri::{AppHandle, Manager, RunEvent};
+use tokio::io::split;
+use tokio_serial::SerialPortBuilderExt;
+use tokio_util::codec::FramedRead;
+
+use checksum::{ChecksumKind, ChecksumStats, CHECKSUM_CONFIG, CHECKSUM_STATS};
+use connections::ConnectionKind;
+use emission::{TelemetryBatcher, EMISSION_CONFIG};
+use line_codec::{Frame, FramingStats, FRAMING_STATS, MAX_LINE_LENGTH};
+use link_quality::LINK_QUALITY_CONFIG;
+use link_watchdog::{LinkStatus, LinkWatchdog, LINK_STATUS, LINK_WATCHDOG_CONFIG};
+use packet_types::PacketType;
+use serial_config::{SerialPortInfo, SerialSettings};
+use telemetry_schema::TELEMETRY_SCHEMA;
+use transport::{SerialCodec, SerialFrame};
+use units::DISPLAY_UNITS;
+use xbee::{ApiFrame, FrameError, XBEE_STATS};
+
+/// Team ID assigned by the competition, expected in every packet and command
+const TEAM_ID: i32 = 1082;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A container packet. The fields are read in the order of the telemetry schema, see
+/// `telemetry_schema`, fields missing from the schema keep their default
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(dead_code)]
 struct Telemetry {
     team_id: i32,
@@ -80,73 +128,124 @@ struct Telemetry {
     /// For example, CXON or SP101325. See the command section for details of command
     /// formats. Do not include commas characters
     cmd_echo: String,
+    /// UTC time at which the ground station received the packet, not part of the
+    /// packet itself
+    #[serde(skip_deserializing)]
+    received_utc: Option<DateTime<Utc>>,
+    /// Seconds from opening the connection until the packet was received, measured
+    /// with a monotonic clock so it is immune to changes of the system time
+    #[serde(skip_deserializing)]
+    received_monotonic_s: Option<f64>,
+    /// Signal strength of the radio frame that carried the packet, only known when
+    /// the XBee runs in API mode
+    #[serde(skip_deserializing)]
+    rssi_dbm: Option<i16>,
+    /// Fields of the telemetry schema that have no counterpart above, by field name
+    #[serde(flatten)]
+    extra: BTreeMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct TelemetryCsv {
-    team_id: i32,
-    /// MISSION_TIME is UTC time in format hh:mm:ss, where hh is hours, mm is minutes,
-    /// and ss is seconds. E.g., '13:14:02' indicates 1:14:02 PM
-    mission_time: String,
-    /// PACKET_COUNT is the total count of transmitted packets since turn on, which is to be
-    /// reset to zero by command when the CanSat is installed in the rocket on the launch pad
-    /// at the beginning of the mission and maintained through processor reset.
-    packet_count: i32,
-    /// 'F' for flight mode and 'S' for simulation mode
-    mode: String,
-    /// STATE is the operating state of the software. (e.g., LAUNCH_WAIT, ASCENT,
-    /// ROCKET_SEPARATION, DESCENT, HS_RELEASE, LANDED, etc.). Teams may
-    /// define their own states. This should be a human readable description as the judges
-    /// will review it after the launch in the .csv files
-    state: String,
-    /// ALTITUDE is the altitude in units of meters and must be relative to ground level at the
-    /// launch site. The resolution must be 0.1 meters
-    altitude: String,
-    /// 'P' indicates the Probe with heat shield is deployed, 'N' otherwise
-    hs_deployed: String,
-    /// 'C' indicates the Probe parachute is deployed (at 200 m), 'N' otherwise
-    pc_deployed: String,
-    /// 'M' indicates the flag mast has been raised after landing, 'N' otherwise
-    mast_raised: String,
-    /// TEMPERATURE is the temperature in degrees Celsius with a resolution of 0.1 degrees
-    temperature: String,
-    /// PRESSURE is the air pressure of the sensor used. Value must be in kPa with
-    /// a resolution of 0.1 kPa
-    pressure: String,
-    /// VOLTAGE is the voltage of the CanSat power bus with a resolution of 0.1 volts
-    voltage: String,
-    /// GPS_TIME is the time from the GPS receiver. The time must be reported in UTC and
-    /// have a resolution of a second
-    gps_time: String,
-    /// GPS_ALTITUDE is the altitude from the GPS receiver in meters above mean sea
-    /// level with a resolution of 0.1 meters
-    gps_altitude: String,
-    /// GPS_LATITUDE is the latitude from the GPS receiver in decimal degrees with a
-    /// resolution of 0.0001 degrees North
-    gps_latitude: String,
-    /// GPS_LONGITUDE is the longitude from the GPS receiver in decimal degrees with a
-    /// resolution of 0.0001 degrees West
-    gps_longitude: String,
-    /// GPS_SATS is the number of GPS satellites being tracked by the GPS receiver. This
-    /// must be an integer
-    gps_sats: i32,
-    /// TILT_X, TILT_Y are the angles of the CanSat X and Y axes in degrees, with a
-    /// resolution of 0.01 degrees, where zero degrees is defined as when the axes are
-    /// perpendicular to the Z axis which is defined as towards the center of gravity of the
-    /// Earth
-    tilt_x: String,
-    tilt_y: String,
-    /// CMD_ECHO is the text of the last command received and processed by the CanSat.
-    /// For example, CXON or SP101325. See the command section for details of command
-    /// formats. Do not include commas characters
-    cmd_echo: String,
+/// Parses a hh:mm:ss time of day into seconds since midnight
+fn parse_time_of_day(time: &str) -> Option<f64> {
+    let time = time.trim();
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S%.f"))
+        .ok()?;
+    Some(time.num_seconds_from_midnight() as f64 + time.nanosecond() as f64 / 1e9)
+}
+
+impl Telemetry {
+    /// MISSION_TIME converted to seconds since midnight, `None` if it is malformed
+    fn mission_time_seconds(&self) -> Option<f64> {
+        parse_time_of_day(&self.mission_time)
+    }
+
+    /// GPS_TIME converted to seconds since midnight, `None` if it is malformed
+    fn gps_time_seconds(&self) -> Option<f64> {
+        parse_time_of_day(&self.gps_time)
+    }
+
+    /// Packets sent without a GPS fix report zeroed coordinates
+    fn has_gps_fix(&self) -> bool {
+        self.gps_sats > 0 && (self.gps_latitude != 0.0 || self.gps_longitude != 0.0)
+    }
+}
+
+/// Logs of the running session a connection appends to, with whether each of them
+/// needs a header row
+struct SessionLogs {
+    telemetry: (File, bool),
+    payload: (File, bool),
+    link_quality: (File, bool),
+}
+
+/// Opens the session logs for a new connection. The first telemetry connection
+/// starts a session, further ones, e.g. a backup radio, join the running one. A new
+/// session starts with an empty history, a resumed one keeps the recovered flight
+async fn open_session_logs(
+    app_handle: &AppHandle,
+    device: &str,
+    baudrate: i32,
+    first: bool,
+) -> Result<SessionLogs, String> {
+    let path = match session::current_telemetry_log().await {
+        Some(path) if !first => path,
+        _ => {
+            let resuming = session::resume_pending().await;
+            let path = session::start_session(device, baudrate).await?;
+            if !resuming {
+                history::clear().await;
+                events::clear().await;
+                let payload = packet_types::replace_payload(Vec::new()).await;
+                let telemetry: Vec<Telemetry> = Vec::new();
+                if let Err(e) = app_handle
+                    .emit_all("telemetry-history", telemetry)
+                    .and_then(|_| app_handle.emit_all("payload-history", payload))
+                {
+                    eprintln!("Failed to clear the telemetry of the UI: {}", e);
+                }
+            }
+            path
+        }
+    };
+    let logs = SessionLogs {
+        telemetry: open_log(&path)?,
+        payload: open_log(&path.with_file_name(session::PAYLOAD_LOG_FILE))?,
+        link_quality: open_log(&path.with_file_name(session::LINK_QUALITY_LOG_FILE))?,
+    };
+    history::set_flight_log(Some(path)).await;
+    Ok(logs)
+}
+
+/// Drops a telemetry connection, the session ends with the last one so it is not
+/// offered for recovery
+async fn drop_connection(connection_id: &str) {
+    LINK_STATUS.lock().await.remove(connection_id);
+    if connections::remove(connection_id).await
+        && session::finish_current_session().await
+    {
+        println!("The last connection closed, the session has ended");
+    }
+}
+
+/// Opens a flight log for appending, a resumed session already has one and new rows
+/// are added after it. Also tells whether the file is new and needs a header row
+fn open_log(path: &Path) -> Result<(File, bool), String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Error opening file at {:?}: {}", path, e))?;
+    let is_new = file
+        .metadata()
+        .map(|metadata| metadata.len() == 0)
+        .unwrap_or(true);
+    Ok((file, is_new))
 }
 
 lazy_static! {
-    static ref SHARED_SENDER: Arc<tokio::sync::Mutex<Option<WriteHalf<SerialStream>>>> =
-        Arc::new(tokio::sync::Mutex::new(None));
-    static ref TELEMETRY: Arc<tokio::sync::Mutex<Vec<Telemetry>>> =
-        Arc::new(tokio::sync::Mutex::new(vec![]));
+    static ref TELEMETRY: Arc<tokio::sync::Mutex<VecDeque<Telemetry>>> =
+        Arc::new(tokio::sync::Mutex::new(VecDeque::new()));
     static ref SIMULATION_DATA: Arc<tokio::sync::Mutex<Vec<SimulationData>>> =
         Arc::new(tokio::sync::Mutex::new(vec![]));
 }
@@ -155,6 +254,16 @@ lazy_static! {
 async fn main() {
     let context = tauri::generate_context!();
 
+    match session::unfinished_session().await {
+        Ok(Some(manifest)) => println!(
+            "Session {} was not finished, it can be recovered",
+            manifest.id
+        ),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to look for unfinished sessions: {}", e),
+    }
+
+    let runtime = tokio::runtime::Handle::current();
     tauri::Builder::default()
         .menu(if cfg!(target_os = "macos") {
             tauri::Menu::os_default(&context.package_info().name)
@@ -235,16 +344,96 @@ async fn main() {
                 .body(buf)
         })
         .on_page_load(move |_, _| {})
+        .setup(|app| {
+            port_watcher::spawn(app.app_handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_serial_ports_command,
+            port_watcher::set_port_watcher_config,
+            port_watcher::get_port_watcher_config,
+            port_watcher::get_remembered_device,
+            port_watcher::forget_device,
             start_connection_and_reading,
+            connections::list_connections,
+            connections::set_primary_connection,
+            connections::close_connection,
+            ground_gps::start_ground_gps,
+            ground_gps::get_ground_position,
+            ground_gps::get_antenna_pointing,
             save_csv,
+            packet_types::save_payload_csv,
+            telemetry_schema::get_telemetry_schema,
+            telemetry_schema::load_telemetry_schema,
+            units::set_display_units,
+            units::get_display_units,
+            xbee::set_xbee_config,
+            xbee::get_xbee_config,
+            xbee::get_xbee_stats,
+            radio_setup::read_radio_config,
+            radio_setup::write_radio_config,
+            link_quality::set_link_quality_config,
+            link_quality::get_link_quality_config,
+            link_quality::get_link_quality_series,
+            load_flight_csv,
             send_message_to_device,
             load_simulation_data,
             start_sending_simulation_data,
+            link_watchdog::set_link_watchdog_config,
+            link_watchdog::get_link_watchdog_config,
+            link_watchdog::get_link_status,
+            line_codec::get_framing_stats,
+            checksum::set_checksum_config,
+            checksum::get_checksum_config,
+            checksum::get_checksum_stats,
+            emission::set_emission_config,
+            emission::get_emission_config,
+            report::generate_session_report,
+            time_sync::synchronise_mission_time,
+            time_sync::get_time_sync_status,
+            packet_timing::get_packet_timing_stats,
+            session::list_sessions,
+            session::get_current_session,
+            session::rename_session,
+            session::tag_session,
+            session::set_session_notes,
+            session::delete_session,
+            session::end_session,
+            recovery::get_recoverable_session,
+            recovery::recover_session,
+            recovery::discard_recoverable_session,
+            track_export::export_kml,
+            track_export::export_gpx,
+            track_export::export_geojson,
+            analysis_export::export_jsonl,
+            analysis_export::export_parquet,
+            flight_compare::compare_flights,
+            history::set_history_config,
+            history::get_history_config,
+            history::get_downsampled_series,
+            store::query_telemetry,
+            store::query_payload_telemetry,
+            store::query_telemetry_series,
+            store::query_commands,
+            store::query_events,
         ])
-        .run(context)
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(move |_, event| {
+            if let RunEvent::Exit = event {
+                // A session left open would be offered for recovery on the next start
+                // like after a crash. The event loop runs inside the runtime, so the
+                // session is finished from another thread
+                let runtime = runtime.clone();
+                let finished = std::thread::spawn(move || {
+                    runtime.block_on(session::finish_current_session())
+                })
+                .join();
+                if finished.is_err() {
+                    eprintln!("Failed to finish the session on exit");
+                }
+            }
+        });
 }
 
 #[tauri::command(rename_all = "snake_case")]
@@ -252,165 +441,481 @@ async fn start_connection_and_reading(
     app_handle: AppHandle,
     device: String,
     baudrate: i32,
+    settings: Option<SerialSettings>,
+    connection_id: Option<String>,
 ) -> Result<(), String> {
-    println!("Connecting to: {}", device);
+    let settings = settings.unwrap_or_default();
+    let connection_id = connection_id.unwrap_or_else(|| device.clone());
+    connections::check_available(&connection_id, &device).await?;
+    println!("Connecting to: {} as {}", device, connection_id);
     println!("Connecting with baud rate: {}", baudrate);
+    println!("Connecting with settings: {:?}", settings);
 
-    let mut builder = tokio_serial::new(device, baudrate.try_into().unwrap());
-    builder = builder
-        .flow_control(tokio_serial::FlowControl::None)
-        .stop_bits(tokio_serial::StopBits::One)
-        .parity(tokio_serial::Parity::None);
+    let builder = tokio_serial::new(&device, baudrate.try_into().unwrap());
+    let builder = settings.configure(builder);
 
     match builder.open_native_async() {
-        Ok(serial_stream) => {
-            let (mut read_port, write_port) = split(serial_stream);
+        Ok(mut serial_stream) => {
+            settings.apply_control_lines(&mut serial_stream)?;
+            let (read_port, write_port) = split(serial_stream);
 
             println!("Connected!");
+            port_watcher::remember_device(&device).await;
 
-            *SHARED_SENDER.lock().await = Some(write_port);
-            println!("Passed the shared write port to the aliens");
+            let registration = connections::register(
+                &connection_id,
+                ConnectionKind::Telemetry,
+                &device,
+                baudrate,
+                settings.framing,
+                write_port,
+            )
+            .await?;
+            let mut stopped = registration.stopped;
 
             println!("Spawning reading thread");
-            // Add a temp file
-            let now: DateTime<Utc> = Utc::now();
-            let filename = format!(
-                "log_flight_data_{:04}{:02}{:02}_{:02}_{:02}_{:02}_UTC.txt",
-                now.year(),
-                now.month(),
-                now.day(),
-                now.hour(),
-                now.minute(),
-                now.second()
-            );
-
-            let mut path = match dirs::home_dir() {
-                Some(path) => path,
-                None => {
-                    panic!("Failed to create temp file!");
+            let logs = match open_session_logs(
+                &app_handle,
+                &device,
+                baudrate,
+                registration.first,
+            )
+            .await
+            {
+                Ok(logs) => logs,
+                Err(e) => {
+                    drop_connection(&connection_id).await;
+                    return Err(e);
                 }
             };
+            connections::emit_changed(&app_handle).await;
 
-            // Add the ".gcs" directory to the path
-            path.push(".gcs");
-
-            // Create the directory if it doesn't exist
-            std::fs::create_dir_all(&path).unwrap();
-
-            // Add the filename to the path
-            path.push(filename);
-
-            let temp_file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(&path)
-                .map_err(|e| format!("Error opening file at {:?}: {}", path, e))?;
+            let (temp_file, write_headers) = logs.telemetry;
+            let (payload_file, write_payload_headers) = logs.payload;
+            let (quality_file, write_quality_headers) = logs.link_quality;
 
             // Read task
             tokio::spawn(async move {
-                let mut message = String::new();
+                let mut lines = FramedRead::new(
+                    read_port,
+                    SerialCodec::new(settings.framing, MAX_LINE_LENGTH),
+                );
+                if registration.first {
+                    *FRAMING_STATS.lock().await = FramingStats::default();
+                    xbee::reset_stats().await;
+                    *CHECKSUM_STATS.lock().await = ChecksumStats::default();
+                    LINK_STATUS.lock().await.clear();
+                }
+                packet_timing::reset(&connection_id, registration.first).await;
+                link_quality::reset(&connection_id, registration.first).await;
+                // A new schema takes effect with the next connection
+                let schema = TELEMETRY_SCHEMA.lock().await.clone();
                 let mut csv_writer = WriterBuilder::new()
-                    .has_headers(true)
+                    .has_headers(false)
                     .from_writer(temp_file);
+                if write_headers {
+                    let _ = csv_writer.write_record(schema.csv_headers(true));
+                }
+                let mut payload_writer = WriterBuilder::new()
+                    .has_headers(write_payload_headers)
+                    .from_writer(payload_file);
+                let mut quality_writer = WriterBuilder::new()
+                    .has_headers(write_quality_headers)
+                    .from_writer(quality_file);
+                let connected_at = registration.opened_at;
+                let rssi_escaping = settings.framing.api_escaping();
+                let rssi_poll_interval_ms = LINK_QUALITY_CONFIG
+                    .lock()
+                    .await
+                    .rssi_poll_interval_ms
+                    .filter(|_| rssi_escaping.is_some());
+                let mut rssi_poll =
+                    tokio::time::interval(tokio::time::Duration::from_millis(
+                        rssi_poll_interval_ms.unwrap_or(1000),
+                    ));
+                let mut watchdog = LinkWatchdog::new(&connection_id);
+                let check_interval_ms =
+                    LINK_WATCHDOG_CONFIG.lock().await.check_interval_ms;
+                let mut watchdog_interval = tokio::time::interval(
+                    tokio::time::Duration::from_millis(check_interval_ms),
+                );
+                let mut batcher = TelemetryBatcher::new(&connection_id);
+                let mut payload_batcher = TelemetryBatcher::new(&connection_id);
+                let batch_interval_ms = EMISSION_CONFIG.lock().await.batch_interval_ms;
+                let mut batch_interval = tokio::time::interval(
+                    tokio::time::Duration::from_millis(batch_interval_ms),
+                );
                 loop {
-                    match read_port.read_u8().await {
-                        Ok(byte) => {
-                            if byte == b'\n' {
-                                println!("Received: {:?}", message);
-
-                                let mut csv_reader = csv::ReaderBuilder::new()
-                                    .has_headers(false)
-                                    .from_reader(message.as_bytes());
-                                for result in csv_reader.deserialize::<Telemetry>() {
-                                    // let telemetry = result.unwrap();
-                                    let telemetry = match result {
-                                        Ok(new_telemetry) => new_telemetry,
-                                        Err(e) => {
-                                            eprintln!("Failed to deserialize a message from the device: {:?}", e);
-                                            continue;
-                                        }
-                                    };
-                                    // println!("{:#?}", telemetry);
-                                    if telemetry.team_id == 1082 {
-                                        // Write to the temp file
-
-                                        let telemetry_csv = TelemetryCsv {
-                                            team_id: telemetry.team_id,
-                                            mission_time: telemetry
-                                                .mission_time
-                                                .clone(),
-                                            packet_count: telemetry.packet_count,
-                                            mode: telemetry.mode.clone(),
-                                            state: telemetry.state.clone(),
-                                            altitude: format!(
-                                                "{:.1}",
-                                                telemetry.altitude.clone()
-                                            ),
-                                            hs_deployed: telemetry.hs_deployed.clone(),
-                                            pc_deployed: telemetry.pc_deployed.clone(),
-                                            mast_raised: telemetry.mast_raised.clone(),
-                                            temperature: format!(
-                                                "{:.1}",
-                                                telemetry.temperature.clone()
-                                            ),
-                                            pressure: format!(
-                                                "{:.1}",
-                                                telemetry.pressure.clone()
-                                            ),
-                                            voltage: format!(
-                                                "{:.1}",
-                                                telemetry.voltage.clone()
-                                            ),
-                                            gps_time: telemetry.gps_time.clone(),
-                                            gps_altitude: format!(
-                                                "{:.1}",
-                                                telemetry.gps_altitude.clone()
-                                            ),
-                                            gps_latitude: format!(
-                                                "{:.4}",
-                                                telemetry.gps_latitude.clone()
-                                            ),
-                                            gps_longitude: format!(
-                                                "{:.4}",
-                                                telemetry.gps_longitude.clone()
-                                            ),
-                                            gps_sats: telemetry.gps_sats,
-                                            tilt_x: format!(
-                                                "{:.2}",
-                                                telemetry.tilt_x.clone()
+                    let frame = tokio::select! {
+                        frame = lines.next() => frame,
+                        _ = &mut stopped => {
+                            println!("Closing connection {}", connection_id);
+                            break;
+                        }
+                        _ = batch_interval.tick() => {
+                            if let Some(batch) = batcher.take() {
+                                app_handle
+                                    .emit_all("graph-data", batch)
+                                    .expect("failed to emit event");
+                            }
+                            if let Some(batch) = payload_batcher.take() {
+                                app_handle
+                                    .emit_all("payload-data", batch)
+                                    .expect("failed to emit event");
+                            }
+                            continue;
+                        }
+                        _ = rssi_poll.tick(), if rssi_poll_interval_ms.is_some() => {
+                            let escaped = rssi_escaping.unwrap_or_default();
+                            let (frame, _) = xbee::at_command("DB", &[], escaped);
+                            if let Err(e) =
+                                connections::write(Some(&connection_id), &frame).await
+                            {
+                                eprintln!("Failed to poll the signal strength: {}", e);
+                            }
+                            continue;
+                        }
+                        _ = watchdog_interval.tick() => {
+                            // Bytes of a line that is not complete yet
+                            if lines.decoder_mut().take_activity() {
+                                watchdog.byte_received();
+                            }
+                            let config = LINK_WATCHDOG_CONFIG.lock().await.clone();
+                            if let Some(event) = watchdog.check(&config) {
+                                println!(
+                                    "Link status of {} changed: {:?}",
+                                    connection_id, event
+                                );
+                                match event.status {
+                                    LinkStatus::Healthy => {}
+                                    LinkStatus::Degraded => {
+                                        events::record_alarm(
+                                            "link_degraded",
+                                            format!(
+                                                "No valid packet on {} for {} ms",
+                                                connection_id, event.since_last_packet_ms
                                             ),
-                                            tilt_y: format!(
-                                                "{:.2}",
-                                                telemetry.tilt_y.clone()
+                                        )
+                                        .await
+                                    }
+                                    LinkStatus::Silent => {
+                                        events::record_alarm(
+                                            "link_silent",
+                                            format!(
+                                                "Nothing received on {} for {} ms",
+                                                connection_id, event.since_last_byte_ms
                                             ),
-                                            cmd_echo: telemetry.cmd_echo.clone(),
-                                        };
-
-                                        let _ =
-                                            csv_writer.serialize(telemetry_csv.clone());
-                                        let _ = csv_writer.flush();
-
-                                        let mut all_telemetry = TELEMETRY.lock().await;
-                                        app_handle
-                                            .emit_all("graph-data", telemetry.clone())
-                                            .expect("failed to emit event");
-                                        all_telemetry.push(telemetry.clone());
-                                    } else {
-                                        println!("The received packet didnt have team is 1082")
+                                        )
+                                        .await
                                     }
                                 }
+                                connections::set_link_status(&connection_id, event.status)
+                                    .await;
+                                LINK_STATUS
+                                    .lock()
+                                    .await
+                                    .insert(connection_id.clone(), event.clone());
+                                app_handle
+                                    .emit_all("link-status", event)
+                                    .expect("failed to emit event");
+                                connections::emit_changed(&app_handle).await;
+                            }
+                            continue;
+                        }
+                    };
+                    let frame = match frame {
+                        Some(Ok(frame)) => frame,
+                        Some(Err(e)) => {
+                            eprintln!("Failed to read from serial_port: {}", e);
+                            continue;
+                        }
+                        None => {
+                            println!("The serial port of {} was closed", connection_id);
+                            break;
+                        }
+                    };
+                    watchdog.byte_received();
+                    let (frame, rssi_dbm) = match frame {
+                        SerialFrame::Line { frame, rssi_dbm } => {
+                            if let Some(rssi_dbm) = rssi_dbm {
+                                XBEE_STATS.lock().await.last_rssi_dbm = Some(rssi_dbm);
+                                connections::set_rssi(&connection_id, rssi_dbm).await;
+                            }
+                            (frame, rssi_dbm)
+                        }
+                        SerialFrame::Api(ApiFrame::TransmitStatus {
+                            frame_id,
+                            status,
+                            retries,
+                        }) => {
+                            let status =
+                                xbee::transmit_status(frame_id, status, retries).await;
+                            println!("Transmit status: {:?}", status);
+                            link_quality::record_transmit_status(
+                                &connection_id,
+                                &status,
+                            )
+                            .await;
+                            if !status.delivered {
+                                events::record_alarm(
+                                    "command_not_delivered",
+                                    format!(
+                                        "{} was not delivered: {}",
+                                        status
+                                            .command
+                                            .as_deref()
+                                            .unwrap_or("A command"),
+                                        status.description
+                                    ),
+                                )
+                                .await;
+                            }
+                            app_handle
+                                .emit_all("transmit-status", status)
+                                .expect("failed to emit event");
+                            continue;
+                        }
+                        SerialFrame::Api(ApiFrame::Corrupted { discarded, error }) => {
+                            eprintln!(
+                                "Dropped {} bytes of API frames, {}",
+                                discarded, error
+                            );
+                            let mut stats = XBEE_STATS.lock().await;
+                            stats.discarded_bytes += discarded as u64;
+                            if error == FrameError::ChecksumMismatch {
+                                stats.checksum_errors += 1;
+                            }
+                            continue;
+                        }
+                        SerialFrame::Api(ApiFrame::AtResponse {
+                            command,
+                            status,
+                            data,
+                            ..
+                        }) if command == "DB" => {
+                            // The value is the magnitude of the RSSI in -dBm
+                            match data.first() {
+                                Some(rssi) if status == 0 => {
+                                    let rssi_dbm = -(*rssi as i16);
+                                    XBEE_STATS.lock().await.last_rssi_dbm =
+                                        Some(rssi_dbm);
+                                    connections::set_rssi(&connection_id, rssi_dbm)
+                                        .await;
+                                    link_quality::record_polled_rssi(
+                                        &connection_id,
+                                        rssi_dbm,
+                                    )
+                                    .await;
+                                }
+                                _ => eprintln!(
+                                    "The radio did not report the signal strength"
+                                ),
+                            }
+                            continue;
+                        }
+                        SerialFrame::Api(frame) => {
+                            println!("Ignoring API frame: {:?}", frame);
+                            continue;
+                        }
+                    };
+                    FRAMING_STATS.lock().await.record(&frame);
+                    let message = match frame {
+                        Frame::Line(line) => line,
+                        Frame::InvalidUtf8(line) => {
+                            eprintln!(
+                                "Received a line that is not valid UTF-8: {:?}",
+                                line
+                            );
+                            session::log_raw_line(&connection_id, &line).await;
+                            store::insert_raw_line(&line).await;
+                            reject_packet(&connection_id).await;
+                            continue;
+                        }
+                        Frame::Overflow { discarded } => {
+                            eprintln!(
+                                "Discarded {} bytes of a line longer than {} bytes",
+                                discarded, MAX_LINE_LENGTH
+                            );
+                            reject_packet(&connection_id).await;
+                            continue;
+                        }
+                    };
+                    println!("Received: {:?}", message);
+                    session::log_raw_line(&connection_id, &message).await;
+                    store::insert_raw_line(&message).await;
 
-                                message.clear();
-                            } else {
-                                message.push(char::from(byte));
+                    let checksum_config = CHECKSUM_CONFIG.lock().await.clone();
+                    let payload = match checksum::verify(
+                        &message,
+                        checksum_config.telemetry,
+                        checksum_config.required,
+                    ) {
+                        Ok(payload) => {
+                            if checksum_config.telemetry != ChecksumKind::None {
+                                let mut stats = CHECKSUM_STATS.lock().await;
+                                if payload.len() < message.len() {
+                                    stats.verified += 1;
+                                } else {
+                                    stats.unverified += 1;
+                                }
                             }
+                            payload
                         }
                         Err(e) => {
-                            eprintln!("Failed to read from serial_port: {}", e);
+                            eprintln!("Rejected a line, {}: {:?}", e, message);
+                            CHECKSUM_STATS.lock().await.record_rejection(&message, &e);
+                            reject_packet(&connection_id).await;
+                            continue;
+                        }
+                    };
+
+                    if packet_types::identify(payload, schema.field_count())
+                        == Some(PacketType::Payload)
+                    {
+                        let mut packet = match packet_types::parse_payload(payload) {
+                            Ok(packet) => packet,
+                            Err(e) => {
+                                eprintln!(
+                                    "Failed to deserialize a payload packet: {}",
+                                    e
+                                );
+                                reject_packet(&connection_id).await;
+                                continue;
+                            }
+                        };
+                        if packet.team_id != TEAM_ID {
+                            println!(
+                                "The received payload packet has a foreign team ID"
+                            );
+                            reject_packet(&connection_id).await;
+                            continue;
+                        }
+                        watchdog.packet_received();
+                        connections::count_packet(&connection_id, true).await;
+                        if !connections::first_reception(
+                            &connection_id,
+                            PacketType::Payload,
+                            packet.packet_count,
+                            &packet.mission_time,
+                        )
+                        .await
+                        {
+                            continue;
                         }
+                        session::count_packet(true).await;
+                        packet.received_utc = Some(Utc::now());
+                        packet.received_monotonic_s =
+                            Some(connected_at.elapsed().as_secs_f64());
+                        packet.rssi_dbm = rssi_dbm;
+                        let _ = payload_writer.serialize(&packet);
+                        let _ = payload_writer.flush();
+                        store::insert_payload(&packet).await;
+                        packet_types::push_payload(packet.clone()).await;
+                        let config = EMISSION_CONFIG.lock().await.clone();
+                        if let Some(batch) = payload_batcher.push(packet, &config) {
+                            app_handle
+                                .emit_all("payload-data", batch)
+                                .expect("failed to emit event");
+                        }
+                        continue;
+                    }
+
+                    let mut telemetry = match schema.parse_line(payload) {
+                        Ok(telemetry) => telemetry,
+                        Err(e) => {
+                            eprintln!(
+                                "Failed to deserialize a message from the device: {}",
+                                e
+                            );
+                            reject_packet(&connection_id).await;
+                            continue;
+                        }
+                    };
+                    if telemetry.team_id == TEAM_ID {
+                        watchdog.packet_received();
+                        connections::count_packet(&connection_id, true).await;
+                        telemetry.received_utc = Some(Utc::now());
+                        telemetry.received_monotonic_s =
+                            Some(connected_at.elapsed().as_secs_f64());
+                        telemetry.rssi_dbm = rssi_dbm;
+                        // Timing and link quality are kept for every radio, a copy
+                        // that another radio received first still tells how this
+                        // one hears the probe
+                        let timing =
+                            packet_timing::record_packet(&connection_id, &telemetry)
+                                .await;
+                        app_handle
+                            .emit_all("packet-timing", timing)
+                            .expect("failed to emit event");
+                        let pointing = ground_gps::track_probe(&telemetry).await;
+                        let quality = link_quality::record_packet(
+                            &connection_id,
+                            &telemetry,
+                            pointing.as_ref(),
+                        )
+                        .await;
+                        let _ = quality_writer.serialize(&quality);
+                        let _ = quality_writer.flush();
+                        app_handle
+                            .emit_all("link-quality", quality)
+                            .expect("failed to emit event");
+                        if !connections::first_reception(
+                            &connection_id,
+                            PacketType::Container,
+                            telemetry.packet_count,
+                            &telemetry.mission_time,
+                        )
+                        .await
+                        {
+                            continue;
+                        }
+                        session::count_packet(true).await;
+                        // Write to the temp file
+
+                        let _ = csv_writer
+                            .write_record(schema.csv_record(&telemetry, true));
+                        let _ = csv_writer.flush();
+                        store::insert_telemetry(&telemetry).await;
+
+                        history::push(telemetry.clone()).await;
+                        let config = EMISSION_CONFIG.lock().await.clone();
+                        let units = *DISPLAY_UNITS.lock().await;
+                        let display = schema.to_display(&telemetry, &units);
+                        if let Some(batch) = batcher.push(display, &config) {
+                            app_handle
+                                .emit_all("graph-data", batch)
+                                .expect("failed to emit event");
+                        }
+
+                        if let Some(status) = time_sync::observe(&telemetry).await {
+                            app_handle
+                                .emit_all("time-sync", status)
+                                .expect("failed to emit event");
+                        }
+                        if let Some(pointing) = pointing {
+                            app_handle
+                                .emit_all("antenna-pointing", pointing)
+                                .expect("failed to emit event");
+                        }
+                    } else {
+                        println!("The received packet didnt have team is 1082");
+                        reject_packet(&connection_id).await;
                     }
                 }
+
+                // Whatever arrived since the last batch
+                if let Some(batch) = batcher.take() {
+                    app_handle
+                        .emit_all("graph-data", batch)
+                        .expect("failed to emit event");
+                }
+                if let Some(batch) = payload_batcher.take() {
+                    app_handle
+                        .emit_all("payload-data", batch)
+                        .expect("failed to emit event");
+                }
+                drop_connection(&connection_id).await;
+                connections::emit_changed(&app_handle).await;
             });
 
             Ok(())
@@ -421,9 +926,7 @@ async fn start_connection_and_reading(
 
 #[tauri::command(rename_all = "snake_case")]
 async fn save_csv(output_file: String) -> Result<(), String> {
-    println!("Got the lock");
-    let telemetry = TELEMETRY.lock().await;
-    let telemetry = telemetry.clone();
+    let telemetry = history::whole_flight().await?;
 
     let file = OpenOptions::new()
         .write(true)
@@ -432,33 +935,16 @@ async fn save_csv(output_file: String) -> Result<(), String> {
         .open(&output_file)
         .map_err(|e| format!("Error opening file at {}: {}", output_file, e))?;
 
-    let mut csv_writer = WriterBuilder::new().has_headers(true).from_writer(file);
+    let schema = TELEMETRY_SCHEMA.lock().await.clone();
+    let mut csv_writer = WriterBuilder::new().has_headers(false).from_writer(file);
+    csv_writer
+        .write_record(schema.csv_headers(false))
+        .map_err(|e| format!("Error writing CSV data: {}", e))?;
 
     for t in telemetry.iter() {
-        let telemetry_csv = TelemetryCsv {
-            team_id: t.team_id,
-            mission_time: t.mission_time.clone(),
-            packet_count: t.packet_count,
-            mode: t.mode.clone(),
-            state: t.state.clone(),
-            altitude: format!("{:.1}", t.altitude.clone()),
-            hs_deployed: t.hs_deployed.clone(),
-            pc_deployed: t.pc_deployed.clone(),
-            mast_raised: t.mast_raised.clone(),
-            temperature: format!("{:.1}", t.temperature.clone()),
-            pressure: format!("{:.1}", t.pressure.clone()),
-            voltage: format!("{:.1}", t.voltage.clone()),
-            gps_time: t.gps_time.clone(),
-            gps_altitude: format!("{:.1}", t.gps_altitude.clone()),
-            gps_latitude: format!("{:.4}", t.gps_latitude.clone()),
-            gps_longitude: format!("{:.4}", t.gps_longitude.clone()),
-            gps_sats: t.gps_sats,
-            tilt_x: format!("{:.2}", t.tilt_x.clone()),
-            tilt_y: format!("{:.2}", t.tilt_y.clone()),
-            cmd_echo: t.cmd_echo.clone(),
-        };
+        // The competition CSV has a fixed format without the receive times
         csv_writer
-            .serialize(telemetry_csv)
+            .write_record(schema.csv_record(t, false))
             .map_err(|e| format!("Error writing CSV data: {}", e))?;
     }
     csv_writer
@@ -468,42 +954,124 @@ async fn save_csv(output_file: String) -> Result<(), String> {
     Ok(())
 }
 
-fn get_serial_ports() -> Vec<String> {
-    match available_ports() {
-        Ok(ports) => ports.into_iter().map(|p| p.port_name).collect(),
-        Err(e) => {
-            eprintln!("Failed to get serial ports: {}", e);
-            Vec::new()
-        }
+/// Telemetry in the display units, for the `telemetry-history` event
+async fn display_telemetry(telemetry: &[Telemetry]) -> Vec<Telemetry> {
+    let units = *DISPLAY_UNITS.lock().await;
+    let schema = TELEMETRY_SCHEMA.lock().await;
+    telemetry
+        .iter()
+        .map(|t| schema.to_display(t, &units))
+        .collect()
+}
+
+/// Result of loading a saved flight
+#[derive(Debug, Clone, Serialize)]
+struct FlightImport {
+    /// Packets read from the file
+    loaded: usize,
+    /// Rows of the file that could not be parsed
+    skipped: usize,
+    /// Packets in memory after the import
+    total: usize,
+}
+
+/// Loads a CSV written by `save_csv` or another competition format CSV. The flight
+/// replaces the telemetry in memory, or with `merge` is added to it, skipping packets
+/// that are already present. The resulting telemetry is sent to the UI as a single
+/// `telemetry-history` event
+#[tauri::command(rename_all = "snake_case")]
+async fn load_flight_csv(
+    app_handle: AppHandle,
+    input_file: String,
+    merge: bool,
+) -> Result<FlightImport, String> {
+    let (flight, skipped) = TELEMETRY_SCHEMA
+        .lock()
+        .await
+        .read_csv(Path::new(&input_file))?;
+    if flight.is_empty() {
+        return Err(format!("No telemetry could be read from {}", input_file));
     }
+    let loaded = flight.len();
+    println!(
+        "Loaded {} packets from {}, skipped {} rows",
+        loaded, input_file, skipped
+    );
+
+    let telemetry = if merge {
+        let mut telemetry = history::snapshot().await;
+        let mut present: HashSet<(i32, String, String)> = telemetry
+            .iter()
+            .map(|t| (t.packet_count, t.mission_time.clone(), t.mode.clone()))
+            .collect();
+        for t in flight {
+            if present.insert((t.packet_count, t.mission_time.clone(), t.mode.clone()))
+            {
+                telemetry.push(t);
+            }
+        }
+        // MISSION_TIME starts over at midnight UTC, the receive time does not. Packets
+        // of a competition CSV have no receive time and are ordered by their count,
+        // ahead of the received ones
+        telemetry.sort_by_key(|t| (t.received_utc, t.packet_count));
+        history::replace(telemetry).await
+    } else {
+        // The loaded flight is not in the telemetry log of a session
+        history::set_flight_log(None).await;
+        history::replace(flight).await
+    };
+
+    app_handle
+        .emit_all("telemetry-history", display_telemetry(&telemetry).await)
+        .map_err(|e| format!("Failed to send the loaded telemetry: {}", e))?;
+
+    Ok(FlightImport {
+        loaded,
+        skipped,
+        total: telemetry.len(),
+    })
 }
 
+/// The serial ports of the system, with the USB IDs and names of the devices behind
+/// them
 #[tauri::command(rename_all = "snake_case")]
-fn get_serial_ports_command() -> Vec<String> {
+fn get_serial_ports_command() -> Vec<SerialPortInfo> {
     println!("Fetching serial ports");
-    get_serial_ports()
+    serial_config::list_ports()
 }
 
-#[tauri::command]
-async fn send_message_to_device(message: String) -> Result<(), String> {
+/// Counts a line that did not yield a packet, in the session and for the connection
+async fn reject_packet(connection_id: &str) {
+    session::count_packet(false).await;
+    connections::count_packet(connection_id, false).await;
+}
+
+/// Sends a command to the probe through the given connection, by default the primary
+/// one
+#[tauri::command(rename_all = "snake_case")]
+async fn send_message_to_device(
+    message: String,
+    connection_id: Option<String>,
+) -> Result<(), String> {
     println!("About to send");
-    let new_message = message + "\r\n";
-    let mut shared_sender_lock = SHARED_SENDER.lock().await;
-    println!("Got lock on the sender");
-    if let Some(shared_sender) = shared_sender_lock.as_mut() {
-        println!("Got shared sender as mut");
-        match shared_sender.write_all(new_message.as_bytes()).await {
-            Ok(_) => {
-                println!("Wrote command to port");
-            }
-            Err(e) => {
-                eprintln!("Failed to write to port: {}", e);
-            }
+    let (connection_id, framing) =
+        connections::resolve(connection_id.as_deref()).await?;
+    let checksum_kind = CHECKSUM_CONFIG.lock().await.commands;
+    let new_message = format!("{}\r\n", checksum::append(&message, checksum_kind));
+    let new_message = match framing.api_escaping() {
+        Some(escaped) => {
+            xbee::transmit_request(new_message.as_bytes(), &message, escaped).await?
         }
-        Ok(())
-    } else {
-        Err("No connected device found.".to_string())
+        None => new_message.into_bytes(),
+    };
+    if let Err(e) = connections::write(Some(&connection_id), &new_message).await {
+        eprintln!("{}", e);
+        return Err(e);
     }
+    println!("Wrote command to {}", connection_id);
+    events::record_command(&message).await;
+    session::log_command(&message).await;
+    Ok(())
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -526,7 +1094,7 @@ impl SimulationData {
 #[tauri::command]
 async fn load_simulation_data(simulation_data_path: String) -> Result<usize, String> {
     println!("Starting to read sim data");
-    let file = File::open(simulation_data_path).map_err(|err| err.to_string())?;
+    let file = File::open(&simulation_data_path).map_err(|err| err.to_string())?;
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
         .comment(Some(b'#'))
@@ -545,7 +1113,7 @@ async fn load_simulation_data(simulation_data_path: String) -> Result<usize, Str
 
                 // Replace $ with team id
                 if record.team_id == "$" {
-                    record.team_id = "1082".to_string();
+                    record.team_id = TEAM_ID.to_string();
                 }
 
                 simulation_data.push(record);
@@ -562,6 +1130,11 @@ async fn load_simulation_data(simulation_data_path: String) -> Result<usize, Str
     }
 
     *SIMULATION_DATA.lock().await = simulation_data;
+    // The data is loaded either way, a missing copy in the session is not worth
+    // failing the simulation over
+    if let Err(e) = session::attach_simulation_file(&simulation_data_path).await {
+        eprintln!("Failed to archive the simulation file: {}", e);
+    }
 
     Ok(SIMULATION_DATA.lock().await.len())
 }
@@ -581,7 +1154,7 @@ async fn start_sending_simulation_data() -> Result<(), String> {
 
             let command_string = data.as_command_string();
 
-            if let Err(e) = send_message_to_device(command_string).await {
+            if let Err(e) = send_message_to_device(command_string, None).await {
                 // handle the error here, maybe with `println!` or `log::error!`
                 println!("Error sending message to device: {}", e);
             }
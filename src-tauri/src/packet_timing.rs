@@ -0,0 +1,168 @@
+use crate::connections;
+use crate::time_sync::wrap_offset;
+use crate::Telemetry;
+use chrono::{DateTime, Timelike, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// The competition requires telemetry at 1 Hz
+const NOMINAL_INTERVAL_S: f64 = 1.0;
+/// Intervals longer than this fraction of the nominal one count as late packets
+const LATE_FACTOR: f64 = 1.5;
+/// Intervals shorter than this fraction of the nominal one count as bursts, which
+/// usually means the radio link buffered packets and released them at once
+const BURST_FACTOR: f64 = 0.5;
+
+lazy_static! {
+    /// Statistics of every connection by its ID, duplicates received by several
+    /// radios count for each of them
+    static ref PACKET_TIMING: Arc<tokio::sync::Mutex<BTreeMap<String, PacketTiming>>> =
+        Arc::new(tokio::sync::Mutex::new(BTreeMap::new()));
+}
+
+/// Running statistics over the intervals between received packets, kept with
+/// Welford's algorithm so a long session does not need to store every interval
+#[derive(Default)]
+struct PacketTiming {
+    packets: u64,
+    first_received_utc: Option<DateTime<Utc>>,
+    last_received_utc: Option<DateTime<Utc>>,
+    last_received_monotonic_s: Option<f64>,
+    intervals: u64,
+    mean_interval_s: f64,
+    interval_m2: f64,
+    min_interval_s: Option<f64>,
+    max_interval_s: Option<f64>,
+    late_packets: u64,
+    burst_packets: u64,
+    lag_samples: u64,
+    lag_sum_s: f64,
+}
+
+/// Payload of the `packet-timing` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PacketTimingStats {
+    pub connection_id: String,
+    pub packets: u64,
+    pub first_received_utc: Option<DateTime<Utc>>,
+    pub last_received_utc: Option<DateTime<Utc>>,
+    /// Mean time between consecutive packets, in seconds
+    pub mean_interval_s: Option<f64>,
+    /// Standard deviation of the time between consecutive packets, in seconds
+    pub jitter_s: Option<f64>,
+    pub min_interval_s: Option<f64>,
+    pub max_interval_s: Option<f64>,
+    /// Packets per second derived from the mean interval
+    pub rate_hz: Option<f64>,
+    /// Packets that arrived more than 1.5 nominal intervals after the previous one
+    pub late_packets: u64,
+    /// Packets that arrived less than half a nominal interval after the previous one
+    pub burst_packets: u64,
+    /// Mean of receive UTC minus MISSION_TIME, in seconds. Only meaningful once the
+    /// probe clock is synchronised
+    pub mean_mission_time_lag_s: Option<f64>,
+}
+
+impl PacketTiming {
+    fn record(&mut self, telemetry: &Telemetry) {
+        let (received_utc, received_monotonic_s) =
+            match (telemetry.received_utc, telemetry.received_monotonic_s) {
+                (Some(utc), Some(monotonic)) => (utc, monotonic),
+                _ => return,
+            };
+
+        self.packets += 1;
+        self.first_received_utc.get_or_insert(received_utc);
+        self.last_received_utc = Some(received_utc);
+
+        if let Some(previous) = self.last_received_monotonic_s {
+            let interval = received_monotonic_s - previous;
+            self.intervals += 1;
+            let delta = interval - self.mean_interval_s;
+            self.mean_interval_s += delta / self.intervals as f64;
+            self.interval_m2 += delta * (interval - self.mean_interval_s);
+            self.min_interval_s = Some(
+                self.min_interval_s
+                    .map_or(interval, |min| min.min(interval)),
+            );
+            self.max_interval_s = Some(
+                self.max_interval_s
+                    .map_or(interval, |max| max.max(interval)),
+            );
+            if interval > NOMINAL_INTERVAL_S * LATE_FACTOR {
+                self.late_packets += 1;
+            } else if interval < NOMINAL_INTERVAL_S * BURST_FACTOR {
+                self.burst_packets += 1;
+            }
+        }
+        self.last_received_monotonic_s = Some(received_monotonic_s);
+
+        if let Some(mission_time) = telemetry.mission_time_seconds() {
+            let received = received_utc.num_seconds_from_midnight() as f64
+                + received_utc.nanosecond() as f64 / 1e9;
+            self.lag_samples += 1;
+            self.lag_sum_s += wrap_offset(received - mission_time);
+        }
+    }
+
+    fn stats(&self, connection_id: &str) -> PacketTimingStats {
+        let has_intervals = self.intervals > 0;
+        PacketTimingStats {
+            connection_id: connection_id.to_string(),
+            packets: self.packets,
+            first_received_utc: self.first_received_utc,
+            last_received_utc: self.last_received_utc,
+            mean_interval_s: has_intervals.then_some(self.mean_interval_s),
+            jitter_s: has_intervals
+                .then(|| (self.interval_m2 / self.intervals as f64).sqrt()),
+            min_interval_s: self.min_interval_s,
+            max_interval_s: self.max_interval_s,
+            rate_hz: (has_intervals && self.mean_interval_s > 0.0)
+                .then(|| 1.0 / self.mean_interval_s),
+            late_packets: self.late_packets,
+            burst_packets: self.burst_packets,
+            mean_mission_time_lag_s: (self.lag_samples > 0)
+                .then(|| self.lag_sum_s / self.lag_samples as f64),
+        }
+    }
+}
+
+/// Starts the statistics of a new connection, with `new_session` the ones of the
+/// other connections are dropped too
+pub async fn reset(connection_id: &str, new_session: bool) {
+    let mut timing = PACKET_TIMING.lock().await;
+    if new_session {
+        timing.clear();
+    }
+    timing.insert(connection_id.to_string(), PacketTiming::default());
+}
+
+/// Adds a packet received on a connection to its statistics and returns the updated
+/// values
+pub async fn record_packet(
+    connection_id: &str,
+    telemetry: &Telemetry,
+) -> PacketTimingStats {
+    let mut timing = PACKET_TIMING.lock().await;
+    let timing = timing.entry(connection_id.to_string()).or_default();
+    timing.record(telemetry);
+    timing.stats(connection_id)
+}
+
+/// The statistics of a connection, by default the primary one
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_packet_timing_stats(
+    connection_id: Option<String>,
+) -> Option<PacketTimingStats> {
+    let connection_id = match connection_id {
+        Some(connection_id) => connection_id,
+        None => connections::primary_id().await?,
+    };
+    PACKET_TIMING
+        .lock()
+        .await
+        .get(&connection_id)
+        .map(|timing| timing.stats(&connection_id))
+}
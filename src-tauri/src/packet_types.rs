@@ -0,0 +1,284 @@
+use crate::history::HISTORY_CONFIG;
+use chrono::{DateTime, Utc};
+use csv::WriterBuilder;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Arc;
+
+/// PACKET_TYPE value of payload packets, container packets carry MODE in its place
+const PAYLOAD_TYPE_TAG: &str = "P";
+
+lazy_static! {
+    pub static ref PAYLOAD_TELEMETRY: Arc<tokio::sync::Mutex<VecDeque<PayloadTelemetry>>> =
+        Arc::new(tokio::sync::Mutex::new(VecDeque::new()));
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketType {
+    /// The `Telemetry` packet of the container
+    Container,
+    /// The `PayloadTelemetry` packet of the released payload
+    Payload,
+}
+
+/// Tells the packet types apart by the type tag in the fourth field, falling back to
+/// the number of fields of a container packet in the telemetry schema. `None` if the
+/// line matches neither
+pub fn identify(line: &str, container_fields: usize) -> Option<PacketType> {
+    let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+    if fields.get(3) == Some(&PAYLOAD_TYPE_TAG) {
+        Some(PacketType::Payload)
+    } else if fields.len() == container_fields {
+        Some(PacketType::Container)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadTelemetry {
+    pub team_id: i32,
+    /// MISSION_TIME is UTC time in format hh:mm:ss, taken from the container clock
+    pub mission_time: String,
+    /// PACKET_COUNT counts the packets of the payload, independent of the container
+    pub packet_count: i32,
+    /// PACKET_TYPE is always 'P'
+    pub packet_type: String,
+    /// ALTITUDE in meters relative to the launch site, resolution of 0.1 meters
+    pub altitude: f32,
+    /// TEMPERATURE in degrees Celsius with a resolution of 0.1 degrees
+    pub temperature: f32,
+    /// VOLTAGE of the payload power bus with a resolution of 0.1 volts
+    pub voltage: f32,
+    /// GYRO_R, GYRO_P, GYRO_Y are the roll, pitch and yaw rates in degrees per second
+    pub gyro_r: f32,
+    pub gyro_p: f32,
+    pub gyro_y: f32,
+    /// ACCEL_R, ACCEL_P, ACCEL_Y are the accelerations along the roll, pitch and yaw
+    /// axes in meters per second squared
+    pub accel_r: f32,
+    pub accel_p: f32,
+    pub accel_y: f32,
+    /// MAG_R, MAG_P, MAG_Y are the magnetic field along the roll, pitch and yaw axes
+    /// in gauss
+    pub mag_r: f32,
+    pub mag_p: f32,
+    pub mag_y: f32,
+    /// POINTING_ERROR is the angle in degrees between the camera and its target
+    pub pointing_error: f32,
+    /// STATE is the operating state of the payload software
+    pub state: String,
+    /// UTC time at which the ground station received the packet, not part of the
+    /// packet itself. The receive columns are always written to the session log,
+    /// empty when unknown, so every row has the same width
+    #[serde(default)]
+    pub received_utc: Option<DateTime<Utc>>,
+    /// Seconds from opening the connection until the packet was received
+    #[serde(default)]
+    pub received_monotonic_s: Option<f64>,
+    /// Signal strength of the radio frame that carried the packet, see `Telemetry`
+    #[serde(default)]
+    pub rssi_dbm: Option<i16>,
+}
+
+/// Columns of the competition payload CSV, the packet as the probe sends it
+const COMPETITION_HEADERS: [&str; 18] = [
+    "team_id",
+    "mission_time",
+    "packet_count",
+    "packet_type",
+    "altitude",
+    "temperature",
+    "voltage",
+    "gyro_r",
+    "gyro_p",
+    "gyro_y",
+    "accel_r",
+    "accel_p",
+    "accel_y",
+    "mag_r",
+    "mag_p",
+    "mag_y",
+    "pointing_error",
+    "state",
+];
+
+impl PayloadTelemetry {
+    /// The packet in the order of `COMPETITION_HEADERS`, without the receive times
+    fn competition_record(&self) -> [String; 18] {
+        [
+            self.team_id.to_string(),
+            self.mission_time.clone(),
+            self.packet_count.to_string(),
+            self.packet_type.clone(),
+            self.altitude.to_string(),
+            self.temperature.to_string(),
+            self.voltage.to_string(),
+            self.gyro_r.to_string(),
+            self.gyro_p.to_string(),
+            self.gyro_y.to_string(),
+            self.accel_r.to_string(),
+            self.accel_p.to_string(),
+            self.accel_y.to_string(),
+            self.mag_r.to_string(),
+            self.mag_p.to_string(),
+            self.mag_y.to_string(),
+            self.pointing_error.to_string(),
+            self.state.clone(),
+        ]
+    }
+}
+
+/// Parses a payload packet, the line must already be identified as one
+pub fn parse_payload(line: &str) -> Result<PayloadTelemetry, String> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(line.as_bytes());
+    match csv_reader.deserialize::<PayloadTelemetry>().next() {
+        Some(result) => result.map_err(|e| e.to_string()),
+        None => Err("The payload packet is empty".to_string()),
+    }
+}
+
+/// Keeps a received payload packet in memory, bounded like the container history
+pub async fn push_payload(packet: PayloadTelemetry) {
+    let max_packets = HISTORY_CONFIG.lock().await.max_packets;
+    let mut telemetry = PAYLOAD_TELEMETRY.lock().await;
+    telemetry.push_back(packet);
+    while telemetry.len() > max_packets {
+        telemetry.pop_front();
+    }
+}
+
+/// Replaces the payload packets in memory, e.g. with the ones of a recovered session.
+/// Returns the packets that were kept
+pub async fn replace_payload(packets: Vec<PayloadTelemetry>) -> Vec<PayloadTelemetry> {
+    let max_packets = HISTORY_CONFIG.lock().await.max_packets;
+    let mut telemetry = PAYLOAD_TELEMETRY.lock().await;
+    *telemetry = VecDeque::from(packets);
+    while telemetry.len() > max_packets {
+        telemetry.pop_front();
+    }
+    telemetry.iter().cloned().collect()
+}
+
+/// Reads back a session payload log, returns the packets and the number of rows that
+/// could not be parsed
+pub fn read_payload_csv(path: &Path) -> Result<(Vec<PayloadTelemetry>, usize), String> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Error opening {:?}: {}", path, e))?;
+    let mut packets = Vec::new();
+    let mut skipped = 0;
+    for result in csv_reader.deserialize::<PayloadTelemetry>() {
+        match result {
+            Ok(packet) => packets.push(packet),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((packets, skipped))
+}
+
+/// Writes the payload packets in memory to a CSV file, in the packet format
+#[tauri::command(rename_all = "snake_case")]
+pub async fn save_payload_csv(output_file: String) -> Result<usize, String> {
+    let telemetry = PAYLOAD_TELEMETRY.lock().await.clone();
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&output_file)
+        .map_err(|e| format!("Error opening file at {}: {}", output_file, e))?;
+    let mut csv_writer = WriterBuilder::new().has_headers(false).from_writer(file);
+    csv_writer
+        .write_record(COMPETITION_HEADERS)
+        .map_err(|e| format!("Error writing CSV data: {}", e))?;
+    for t in telemetry.iter() {
+        // Same fixed format as the container CSV, without the receive times
+        csv_writer
+            .write_record(t.competition_record())
+            .map_err(|e| format!("Error writing CSV data: {}", e))?;
+    }
+    csv_writer
+        .flush()
+        .map_err(|e| format!("Error flushing CSV data: {}", e))?;
+
+    Ok(telemetry.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "1082,12:00:01,7,P,512.3,21.5,4.9,0.1,-0.2,0.3,0.0,0.1,9.8,0.2,0.3,0.4,2.5,DESCENT";
+
+    #[test]
+    fn parses_a_packet_without_receive_times() {
+        let packet = parse_payload(LINE).unwrap();
+        assert_eq!(packet.packet_count, 7);
+        assert_eq!(packet.state, "DESCENT");
+        assert_eq!(packet.received_utc, None);
+        assert_eq!(packet.rssi_dbm, None);
+        assert_eq!(identify(LINE, 20), Some(PacketType::Payload));
+    }
+
+    #[test]
+    fn reads_back_a_log_with_and_without_receive_times() {
+        let received_utc = "2026-10-18T12:00:02Z".parse::<DateTime<Utc>>().unwrap();
+        // A transparent connection knows no RSSI, an API connection does
+        let transparent = PayloadTelemetry {
+            received_utc: Some(received_utc),
+            received_monotonic_s: Some(1.5),
+            ..parse_payload(LINE).unwrap()
+        };
+        let api = PayloadTelemetry {
+            packet_count: 8,
+            rssi_dbm: Some(-61),
+            ..transparent.clone()
+        };
+        let recovered = PayloadTelemetry {
+            packet_count: 9,
+            received_utc: None,
+            received_monotonic_s: None,
+            ..transparent.clone()
+        };
+
+        let path = std::env::temp_dir()
+            .join(format!("gcs-payload-test-{}.csv", std::process::id()));
+        let mut csv_writer = WriterBuilder::new().from_path(&path).unwrap();
+        for packet in [&transparent, &api, &recovered] {
+            csv_writer.serialize(packet).unwrap();
+        }
+        csv_writer.flush().unwrap();
+        drop(csv_writer);
+
+        let (packets, skipped) = read_payload_csv(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(skipped, 0);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].received_utc, Some(received_utc));
+        assert_eq!(packets[0].received_monotonic_s, Some(1.5));
+        assert_eq!(packets[0].rssi_dbm, None);
+        assert_eq!(packets[1].rssi_dbm, Some(-61));
+        assert_eq!(packets[2].received_utc, None);
+        assert_eq!(packets[2].packet_count, 9);
+    }
+
+    #[test]
+    fn writes_the_competition_columns_only() {
+        let packet = PayloadTelemetry {
+            rssi_dbm: Some(-61),
+            ..parse_payload(LINE).unwrap()
+        };
+        let record = packet.competition_record();
+        assert_eq!(record.len(), COMPETITION_HEADERS.len());
+        assert_eq!(record.join(","), LINE.replace("0.0,", "0,"));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::serial_config::{list_ports, PortType, SerialPortInfo};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+lazy_static! {
+    pub static ref PORT_WATCHER_CONFIG: Arc<tokio::sync::Mutex<PortWatcherConfig>> =
+        Arc::new(tokio::sync::Mutex::new(PortWatcherConfig::default()));
+    pub static ref REMEMBERED_DEVICE: Arc<tokio::sync::Mutex<Option<DeviceIdentity>>> =
+        Arc::new(tokio::sync::Mutex::new(None));
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PortWatcherConfig {
+    /// How often in milliseconds the port list is polled
+    pub poll_interval_ms: u64,
+}
+
+impl Default for PortWatcherConfig {
+    fn default() -> Self {
+        PortWatcherConfig {
+            poll_interval_ms: 1000,
+        }
+    }
+}
+
+/// A USB device recognised by its IDs rather than its port name, which changes when
+/// the device is plugged back in, e.g. from /dev/ttyUSB0 to /dev/ttyUSB1
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceIdentity {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+}
+
+impl DeviceIdentity {
+    fn of(port: &SerialPortInfo) -> Option<Self> {
+        if port.port_type != PortType::Usb {
+            return None;
+        }
+        Some(DeviceIdentity {
+            vid: port.vid?,
+            pid: port.pid?,
+            serial_number: port.serial_number.clone(),
+        })
+    }
+}
+
+/// Payload of the `ports-changed` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PortsChangedEvent {
+    /// All ports now present
+    pub ports: Vec<SerialPortInfo>,
+    pub added: Vec<SerialPortInfo>,
+    pub removed: Vec<SerialPortInfo>,
+    /// The remembered device if it is among the added ports, for the UI to select
+    pub reappeared: Option<SerialPortInfo>,
+}
+
+/// Remembers the device behind a port after connecting to it, ports that are not USB
+/// devices cannot be recognised again and are not remembered
+pub async fn remember_device(port_name: &str) {
+    let identity = list_ports()
+        .iter()
+        .find(|port| port.name == port_name)
+        .and_then(DeviceIdentity::of);
+    if let Some(identity) = identity {
+        println!("Remembering device {:?} of {}", identity, port_name);
+        *REMEMBERED_DEVICE.lock().await = Some(identity);
+    }
+}
+
+fn ports_changed(
+    known: &[SerialPortInfo],
+    ports: Vec<SerialPortInfo>,
+    remembered: Option<&DeviceIdentity>,
+) -> Option<PortsChangedEvent> {
+    let added: Vec<SerialPortInfo> = ports
+        .iter()
+        .filter(|port| !known.contains(port))
+        .cloned()
+        .collect();
+    let removed: Vec<SerialPortInfo> = known
+        .iter()
+        .filter(|port| !ports.contains(port))
+        .cloned()
+        .collect();
+    if added.is_empty() && removed.is_empty() {
+        return None;
+    }
+    let reappeared = remembered.and_then(|remembered| {
+        added
+            .iter()
+            .find(|port| DeviceIdentity::of(port).as_ref() == Some(remembered))
+            .cloned()
+    });
+    Some(PortsChangedEvent {
+        ports,
+        added,
+        removed,
+        reappeared,
+    })
+}
+
+/// Polls the serial ports in the background for as long as the application runs and
+/// emits a `ports-changed` event whenever a device is plugged in or removed
+pub fn spawn(app_handle: AppHandle) {
+    tokio::spawn(async move {
+        let mut known = list_ports();
+        loop {
+            let poll_interval_ms = PORT_WATCHER_CONFIG.lock().await.poll_interval_ms;
+            tokio::time::sleep(tokio::time::Duration::from_millis(poll_interval_ms))
+                .await;
+
+            let ports = match tokio::task::spawn_blocking(list_ports).await {
+                Ok(ports) => ports,
+                Err(e) => {
+                    eprintln!("Failed to poll the serial ports: {}", e);
+                    continue;
+                }
+            };
+            let remembered = REMEMBERED_DEVICE.lock().await.clone();
+            if let Some(event) = ports_changed(&known, ports, remembered.as_ref()) {
+                println!(
+                    "Serial ports changed, {} added and {} removed",
+                    event.added.len(),
+                    event.removed.len()
+                );
+                known = event.ports.clone();
+                app_handle
+                    .emit_all("ports-changed", event)
+                    .expect("failed to emit event");
+            }
+        }
+    });
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_port_watcher_config(config: PortWatcherConfig) -> Result<(), String> {
+    if config.poll_interval_ms < 100 {
+        return Err("The poll interval must be at least 100 ms.".to_string());
+    }
+    println!("New port watcher config: {:?}", config);
+    *PORT_WATCHER_CONFIG.lock().await = config;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_port_watcher_config() -> PortWatcherConfig {
+    PORT_WATCHER_CONFIG.lock().await.clone()
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_remembered_device() -> Option<DeviceIdentity> {
+    REMEMBERED_DEVICE.lock().await.clone()
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn forget_device() {
+    *REMEMBERED_DEVICE.lock().await = None;
+}
@@ -0,0 +1,294 @@
+use crate::connections;
+use serde::{Deserialize, Serialize};
+use serialport::SerialPort;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+/// Silence the radio needs before and after `+++`, a little over the default GT of 1 s
+const GUARD_TIME: Duration = Duration::from_millis(1100);
+/// Time the radio gets to answer a command
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Baud rates by their BD value
+const BAUD_RATES: [u32; 9] =
+    [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400];
+/// Channels of the 2.4 GHz radios
+const CHANNELS: std::ops::RangeInclusive<u8> = 0x0B..=0x1A;
+
+/// Parameters of the ground radio as read back from it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RadioConfig {
+    /// PAN ID in hex, the probe radio must use the same
+    pub pan_id: String,
+    /// Operating channel, 11 to 26
+    pub channel: u8,
+    /// Baud rate of the serial interface
+    pub baud_rate: Option<u32>,
+    /// 64-bit address the radio sends to in transparent mode, 16 hex digits
+    pub destination_address: String,
+    /// 0 for transparent mode, 1 and 2 for API mode without and with escaping
+    pub api_mode: u8,
+    /// 64-bit address of the radio itself, 16 hex digits
+    pub serial_number: String,
+    pub firmware_version: String,
+}
+
+/// Parameters to change, the ones left out keep their value
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RadioSettings {
+    pub pan_id: Option<String>,
+    pub channel: Option<u8>,
+    pub baud_rate: Option<u32>,
+    pub destination_address: Option<String>,
+    pub api_mode: Option<u8>,
+}
+
+impl RadioSettings {
+    /// The settings as AT commands with their parameters, e.g. ("ID", "3332")
+    fn commands(&self) -> Result<Vec<(&'static str, String)>, String> {
+        let mut commands = Vec::new();
+        if let Some(pan_id) = &self.pan_id {
+            if pan_id.is_empty()
+                || pan_id.len() > 4
+                || !pan_id.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(format!(
+                    "The PAN ID {:?} must be 1 to 4 hex digits.",
+                    pan_id
+                ));
+            }
+            commands.push(("ID", pan_id.to_uppercase()));
+        }
+        if let Some(channel) = self.channel {
+            if !CHANNELS.contains(&channel) {
+                return Err(format!("The channel {} must be from 11 to 26.", channel));
+            }
+            commands.push(("CH", format!("{:X}", channel)));
+        }
+        if let Some(baud_rate) = self.baud_rate {
+            let index = BAUD_RATES
+                .iter()
+                .position(|rate| *rate == baud_rate)
+                .ok_or(format!("The radio does not support {} baud.", baud_rate))?;
+            commands.push(("BD", index.to_string()));
+        }
+        if let Some(address) = &self.destination_address {
+            if address.len() != 16 || !address.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "The destination address {:?} must have 16 hex digits.",
+                    address
+                ));
+            }
+            let address = address.to_uppercase();
+            commands.push(("DH", address[..8].to_string()));
+            commands.push(("DL", address[8..].to_string()));
+        }
+        if let Some(api_mode) = self.api_mode {
+            if api_mode > 2 {
+                return Err(format!("The API mode {} must be 0, 1 or 2.", api_mode));
+            }
+            commands.push(("AP", api_mode.to_string()));
+        }
+        Ok(commands)
+    }
+}
+
+/// A serial port with the radio in AT command mode
+struct CommandMode {
+    port: Box<dyn SerialPort>,
+    /// Parameters the radio accepted, e.g. `ATID3332`
+    accepted: Vec<String>,
+}
+
+impl CommandMode {
+    fn enter(device: &str, baudrate: u32) -> Result<Self, String> {
+        let port = serialport::new(device, baudrate)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .map_err(|e| format!("Error opening {}: {}", device, e))?;
+        let mut mode = CommandMode {
+            port,
+            accepted: Vec::new(),
+        };
+        std::thread::sleep(GUARD_TIME);
+        let _ = mode.port.clear(serialport::ClearBuffer::Input);
+        mode.write("+++")?;
+        std::thread::sleep(GUARD_TIME);
+        match mode.response()?.as_str() {
+            "OK" => Ok(mode),
+            response => Err(format!(
+                "The radio did not enter command mode, it answered {:?}",
+                response
+            )),
+        }
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        self.port
+            .write_all(text.as_bytes())
+            .and_then(|_| self.port.flush())
+            .map_err(|e| format!("Error writing to the radio: {}", e))
+    }
+
+    /// Reads a response up to its carriage return
+    fn response(&mut self) -> Result<String, String> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let mut response = Vec::new();
+        let mut byte = [0u8];
+        while Instant::now() < deadline {
+            match self.port.read(&mut byte) {
+                Ok(1) if byte[0] == b'\r' => {
+                    return Ok(String::from_utf8_lossy(&response).trim().to_string())
+                }
+                Ok(1) => response.push(byte[0]),
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(format!("Error reading from the radio: {}", e)),
+            }
+        }
+        Err("The radio did not answer in time.".to_string())
+    }
+
+    fn query(&mut self, command: &str) -> Result<String, String> {
+        self.write(&format!("AT{}\r", command))?;
+        let response = self.response()?;
+        if response == "ERROR" {
+            return Err(format!("The radio rejected AT{}", command));
+        }
+        Ok(response)
+    }
+
+    fn set(&mut self, command: &str, parameter: &str) -> Result<(), String> {
+        match self.query(&format!("{}{}", command, parameter))?.as_str() {
+            "OK" => {
+                self.accepted.push(format!("AT{}{}", command, parameter));
+                Ok(())
+            }
+            response => Err(format!(
+                "The radio answered {:?} to AT{}{}",
+                response, command, parameter
+            )),
+        }
+    }
+
+    fn read_config(&mut self) -> Result<RadioConfig, String> {
+        let hex = |value: String, command: &str| {
+            u32::from_str_radix(&value, 16)
+                .map_err(|_| format!("Unexpected value {:?} for AT{}", value, command))
+        };
+        let address = |high: String, low: String| {
+            format!("{:0>8}{:0>8}", high, low).to_uppercase()
+        };
+        let baud_rate = hex(self.query("BD")?, "BD")?;
+        Ok(RadioConfig {
+            pan_id: self.query("ID")?.to_uppercase(),
+            channel: hex(self.query("CH")?, "CH")? as u8,
+            baud_rate: BAUD_RATES.get(baud_rate as usize).copied(),
+            destination_address: address(self.query("DH")?, self.query("DL")?),
+            api_mode: hex(self.query("AP")?, "AP")? as u8,
+            serial_number: address(self.query("SH")?, self.query("SL")?),
+            firmware_version: self.query("VR")?,
+        })
+    }
+
+    /// Leaves command mode, which also applies changed parameters such as BD
+    fn exit(mut self) {
+        if let Err(e) = self.query("CN") {
+            eprintln!("Failed to leave command mode: {}", e);
+        }
+    }
+
+    /// Resets the radio without applying the accepted parameters, it starts again
+    /// with the ones saved in its non-volatile memory and leaves command mode
+    fn abort(mut self) -> Result<(), String> {
+        self.set("FR", "")
+    }
+}
+
+/// The port must not be in use by a telemetry connection
+async fn ensure_disconnected(device: &str) -> Result<(), String> {
+    if connections::is_connected(device).await {
+        return Err(
+            "Close the connection to the device before configuring the radio."
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+async fn with_command_mode<T: Send + 'static>(
+    device: String,
+    baudrate: u32,
+    f: impl FnOnce(&mut CommandMode) -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    ensure_disconnected(&device).await?;
+    tokio::task::spawn_blocking(move || {
+        let mut mode = CommandMode::enter(&device, baudrate)?;
+        match f(&mut mode) {
+            Ok(value) => {
+                mode.exit();
+                Ok(value)
+            }
+            Err(e) if mode.accepted.is_empty() => {
+                mode.exit();
+                Err(e)
+            }
+            // Leaving with CN would apply part of the settings, the radio could end
+            // up on a channel or baud rate nothing else uses
+            Err(e) => {
+                let accepted = mode.accepted.join(", ");
+                match mode.abort() {
+                    Ok(()) => Err(format!(
+                        "{}. The radio was reset to its saved parameters, {} were \
+                         not applied",
+                        e, accepted
+                    )),
+                    Err(reset_error) => Err(format!(
+                        "{}. The radio accepted {} and could not be reset ({}), \
+                         power cycle it to discard them",
+                        e, accepted, reset_error
+                    )),
+                }
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("The radio configuration failed: {}", e))?
+}
+
+/// Reads the parameters of the radio on the given port through AT command mode
+#[tauri::command(rename_all = "snake_case")]
+pub async fn read_radio_config(
+    device: String,
+    baudrate: u32,
+) -> Result<RadioConfig, String> {
+    println!("Reading the radio configuration on {}", device);
+    with_command_mode(device, baudrate, |mode| mode.read_config()).await
+}
+
+/// Writes the given parameters to the radio and reads them back. With `save` they are
+/// written to the non-volatile memory of the radio so they survive a power cycle. A
+/// new baud rate applies once command mode is left. If the radio rejects one of them
+/// none is applied, the error names the ones it had accepted
+#[tauri::command(rename_all = "snake_case")]
+pub async fn write_radio_config(
+    device: String,
+    baudrate: u32,
+    settings: RadioSettings,
+    save: bool,
+) -> Result<RadioConfig, String> {
+    let commands = settings.commands()?;
+    println!("Writing {:?} to the radio on {}", commands, device);
+    with_command_mode(device, baudrate, move |mode| {
+        for (command, parameter) in &commands {
+            mode.set(command, parameter)?;
+        }
+        if save {
+            mode.set("WR", "")?;
+        }
+        // Every parameter was accepted, failing to read them back must not undo them
+        mode.accepted.clear();
+        mode.read_config()
+    })
+    .await
+}
@@ -0,0 +1,104 @@
+use crate::display_telemetry;
+use crate::events::{self, SentCommand};
+use crate::history;
+use crate::packet_types;
+use crate::session::{
+    self, SessionManifest, COMMAND_LOG_FILE, PAYLOAD_LOG_FILE, TELEMETRY_LOG_FILE,
+};
+use crate::telemetry_schema::TELEMETRY_SCHEMA;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+/// Reads back the `time<TAB>command` lines of a session command log
+fn read_command_log(path: &Path) -> Vec<SentCommand> {
+    let log = match std::fs::read_to_string(path) {
+        Ok(log) => log,
+        Err(_) => return Vec::new(),
+    };
+    log.lines()
+        .filter_map(|line| {
+            let (time, command) = line.split_once('\t')?;
+            Some(SentCommand {
+                time: DateTime::parse_from_rfc3339(time).ok()?.with_timezone(&Utc),
+                command: command.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Checks whether the previous run of the app left a session unfinished, the UI asks
+/// for it on startup to offer recovering it
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_recoverable_session() -> Result<Option<SessionManifest>, String> {
+    session::unfinished_session().await
+}
+
+/// Reloads the telemetry, payload packets and commands of an unfinished session into
+/// memory and sends them to the UI as single `telemetry-history` and
+/// `payload-history` events. With `keep_appending` the next connection continues the
+/// same session, otherwise the session is marked as finished. Returns the number of recovered packets
+#[tauri::command(rename_all = "snake_case")]
+pub async fn recover_session(
+    app_handle: AppHandle,
+    id: String,
+    keep_appending: bool,
+) -> Result<usize, String> {
+    let directory = session::session_directory(&id)?;
+    println!("Recovering session {} from {:?}", id, directory);
+
+    let telemetry_log = directory.join(TELEMETRY_LOG_FILE);
+    let telemetry = if telemetry_log.exists() {
+        let (telemetry, skipped) =
+            TELEMETRY_SCHEMA.lock().await.read_csv(&telemetry_log)?;
+        if skipped > 0 {
+            println!("Skipped {} damaged rows of the telemetry log", skipped);
+        }
+        telemetry
+    } else {
+        Vec::new()
+    };
+    let payload_log = directory.join(PAYLOAD_LOG_FILE);
+    let payload = if payload_log.exists() {
+        let (payload, skipped) = packet_types::read_payload_csv(&payload_log)?;
+        if skipped > 0 {
+            println!("Skipped {} damaged rows of the payload log", skipped);
+        }
+        payload
+    } else {
+        Vec::new()
+    };
+    let commands = read_command_log(&directory.join(COMMAND_LOG_FILE));
+    println!(
+        "Recovered {} packets, {} payload packets and {} commands",
+        telemetry.len(),
+        payload.len(),
+        commands.len()
+    );
+
+    let telemetry = history::replace(telemetry).await;
+    history::set_flight_log(Some(telemetry_log)).await;
+    events::replace_commands(commands).await;
+    app_handle
+        .emit_all("telemetry-history", display_telemetry(&telemetry).await)
+        .map_err(|e| format!("Failed to send the recovered telemetry: {}", e))?;
+    let payload = packet_types::replace_payload(payload).await;
+    app_handle
+        .emit_all("payload-history", payload)
+        .map_err(|e| format!("Failed to send the recovered payload packets: {}", e))?;
+
+    if keep_appending {
+        session::resume_on_next_connection(&id).await?;
+    } else {
+        session::finish_session(&id).await?;
+    }
+
+    Ok(telemetry.len())
+}
+
+/// Marks an unfinished session as finished without loading it
+#[tauri::command(rename_all = "snake_case")]
+pub async fn discard_recoverable_session(id: String) -> Result<(), String> {
+    session::finish_session(&id).await?;
+    Ok(())
+}
@@ -0,0 +1,845 @@
+use crate::events::{Alarm, SentCommand, ALARMS, SENT_COMMANDS};
+use crate::history;
+use crate::Telemetry;
+use chrono::Utc;
+use std::fmt::Write as _;
+use std::path::Path;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+const CHART_WIDTH: f64 = 720.0;
+const CHART_HEIGHT: f64 = 260.0;
+const CHART_MARGIN: f64 = 48.0;
+
+/// A plottable telemetry field as (name, unit, accessor)
+pub type Sensor = (&'static str, &'static str, fn(&Telemetry) -> f64);
+
+/// Sensors summarised in the report
+const SENSORS: [Sensor; 9] = [
+    ("Altitude", "m", |t| t.altitude as f64),
+    ("Temperature", "°C", |t| t.temperature as f64),
+    ("Pressure", "kPa", |t| t.pressure as f64),
+    ("Voltage", "V", |t| t.voltage as f64),
+    ("GPS altitude", "m", |t| t.gps_altitude as f64),
+    ("GPS satellites", "", |t| t.gps_sats as f64),
+    ("Tilt X", "°", |t| t.tilt_x as f64),
+    ("Tilt Y", "°", |t| t.tilt_y as f64),
+    ("Packet count", "", |t| t.packet_count as f64),
+];
+
+/// Sensors plotted against elapsed mission time
+const CHARTS: [Sensor; 4] = [
+    ("Altitude", "m", |t| t.altitude as f64),
+    ("Temperature", "°C", |t| t.temperature as f64),
+    ("Pressure", "kPa", |t| t.pressure as f64),
+    ("Voltage", "V", |t| t.voltage as f64),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// Uses the explicitly requested format, falling back to the file extension
+    fn from_request(format: Option<&str>, output_file: &str) -> Result<Self, String> {
+        let format = match format {
+            Some(format) => format.to_lowercase(),
+            None => Path::new(output_file)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or("md")
+                .to_lowercase(),
+        };
+        match format.as_str() {
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" | "htm" => Ok(ReportFormat::Html),
+            other => Err(format!("Unsupported report format: {}", other)),
+        }
+    }
+}
+
+/// Format independent building blocks of the report
+enum Block {
+    Heading(String),
+    Paragraph(String),
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    Chart {
+        slug: String,
+        title: String,
+        svg: String,
+    },
+}
+
+struct SeriesStats {
+    min: f64,
+    max: f64,
+    mean: f64,
+}
+
+fn series_stats(values: impl Iterator<Item = f64>) -> Option<SeriesStats> {
+    let mut count = 0;
+    let mut sum = 0.0;
+    let mut min = f64::INFINITY;
+    let mut max = f64::NEG_INFINITY;
+    for value in values.filter(|value| value.is_finite()) {
+        count += 1;
+        sum += value;
+        min = min.min(value);
+        max = max.max(value);
+    }
+    if count == 0 {
+        return None;
+    }
+    Some(SeriesStats {
+        min,
+        max,
+        mean: sum / count as f64,
+    })
+}
+
+/// MISSION_TIME in seconds since midnight of the first packet for every packet,
+/// continuing past 86400 when MISSION_TIME wraps around at midnight
+pub fn mission_seconds<'a>(
+    telemetry: impl IntoIterator<Item = &'a Telemetry>,
+) -> Vec<Option<f64>> {
+    let mut day_offset = 0.0;
+    let mut previous: Option<f64> = None;
+    telemetry
+        .into_iter()
+        .map(|t| {
+            let seconds = t.mission_time_seconds()?;
+            if let Some(previous) = previous {
+                if seconds + SECONDS_PER_DAY / 2.0 < previous {
+                    day_offset += SECONDS_PER_DAY;
+                }
+            }
+            previous = Some(seconds);
+            Some(seconds + day_offset)
+        })
+        .collect()
+}
+
+/// Seconds since the first packet for every packet, accounting for MISSION_TIME
+/// wrapping around at midnight
+pub fn elapsed_seconds(telemetry: &[Telemetry]) -> Vec<Option<f64>> {
+    let mut start: Option<f64> = None;
+    mission_seconds(telemetry)
+        .into_iter()
+        .map(|seconds| {
+            let seconds = seconds?;
+            Some(seconds - *start.get_or_insert(seconds))
+        })
+        .collect()
+}
+
+/// Change of ALTITUDE per second since the previous packet with a usable MISSION_TIME
+pub fn vertical_speeds(
+    telemetry: &[Telemetry],
+    elapsed: &[Option<f64>],
+) -> Vec<Option<f64>> {
+    let mut previous: Option<(f64, f64)> = None;
+    telemetry
+        .iter()
+        .zip(elapsed)
+        .map(|(t, elapsed)| {
+            let elapsed = (*elapsed)?;
+            let altitude = t.altitude as f64;
+            let speed = previous
+                .filter(|(previous_elapsed, _)| elapsed > *previous_elapsed)
+                .map(|(previous_elapsed, previous_altitude)| {
+                    (altitude - previous_altitude) / (elapsed - previous_elapsed)
+                });
+            previous = Some((elapsed, altitude));
+            speed
+        })
+        .collect()
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+pub fn haversine_m(lat_a: f64, lon_a: f64, lat_b: f64, lon_b: f64) -> f64 {
+    let d_lat = (lat_b - lat_a).to_radians();
+    let d_lon = (lon_b - lon_a).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat_a.to_radians().cos()
+            * lat_b.to_radians().cos()
+            * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// A run of consecutive packets reporting the same STATE
+struct StatePhase<'a> {
+    state: &'a str,
+    first: usize,
+    last: usize,
+}
+
+fn state_phases(telemetry: &[Telemetry]) -> Vec<StatePhase<'_>> {
+    let mut phases: Vec<StatePhase> = Vec::new();
+    for (index, t) in telemetry.iter().enumerate() {
+        match phases.last_mut() {
+            Some(phase) if phase.state == t.state => phase.last = index,
+            _ => phases.push(StatePhase {
+                state: &t.state,
+                first: index,
+                last: index,
+            }),
+        }
+    }
+    phases
+}
+
+fn overview(telemetry: &[Telemetry], elapsed: &[Option<f64>]) -> Block {
+    let first = &telemetry[0];
+    let last = &telemetry[telemetry.len() - 1];
+    let duration = elapsed.iter().flatten().cloned().fold(0.0, f64::max);
+
+    let mut rows = vec![
+        vec!["Team ID".to_string(), first.team_id.to_string()],
+        vec![
+            "Mission time".to_string(),
+            format!("{} - {}", first.mission_time, last.mission_time),
+        ],
+        vec!["Flight duration".to_string(), format_duration(duration)],
+    ];
+
+    if let Some((index, apogee)) = telemetry
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.altitude.total_cmp(&b.altitude))
+    {
+        rows.push(vec![
+            "Apogee".to_string(),
+            format!(
+                "{:.1} m at {} ({})",
+                apogee.altitude, apogee.mission_time, apogee.state
+            ),
+        ]);
+        if let Some(seconds) = elapsed[index] {
+            rows.push(vec!["Time to apogee".to_string(), format_duration(seconds)]);
+        }
+    }
+
+    Block::Table {
+        headers: vec!["Item".to_string(), "Value".to_string()],
+        rows,
+    }
+}
+
+fn state_timeline(telemetry: &[Telemetry], elapsed: &[Option<f64>]) -> Block {
+    let rows = state_phases(telemetry)
+        .iter()
+        .map(|phase| {
+            let start = &telemetry[phase.first];
+            let end = &telemetry[phase.last];
+            let duration = match (elapsed[phase.first], elapsed[phase.last]) {
+                (Some(start), Some(end)) => Some(end - start),
+                _ => None,
+            };
+            let descent_rate = match duration {
+                Some(duration) if duration > 0.0 => format!(
+                    "{:.1}",
+                    (start.altitude as f64 - end.altitude as f64) / duration
+                ),
+                _ => "-".to_string(),
+            };
+            vec![
+                phase.state.to_string(),
+                start.mission_time.clone(),
+                end.mission_time.clone(),
+                duration.map_or("-".to_string(), format_duration),
+                (phase.last - phase.first + 1).to_string(),
+                format!("{:.1}", start.altitude),
+                format!("{:.1}", end.altitude),
+                descent_rate,
+            ]
+        })
+        .collect();
+
+    Block::Table {
+        headers: [
+            "State",
+            "Start",
+            "End",
+            "Duration",
+            "Packets",
+            "Start altitude (m)",
+            "End altitude (m)",
+            "Mean descent rate (m/s)",
+        ]
+        .iter()
+        .map(|header| header.to_string())
+        .collect(),
+        rows,
+    }
+}
+
+fn packet_statistics(telemetry: &[Telemetry]) -> Block {
+    let mut missing = 0;
+    let mut duplicated = 0;
+    let mut resets = 0;
+    for pair in telemetry.windows(2) {
+        let (previous, next) = (pair[0].packet_count, pair[1].packet_count);
+        if next == previous {
+            duplicated += 1;
+        } else if next < previous {
+            resets += 1;
+        } else {
+            missing += next - previous - 1;
+        }
+    }
+    let flight_mode = telemetry.iter().filter(|t| t.mode == "F").count();
+    let simulation_mode = telemetry.iter().filter(|t| t.mode == "S").count();
+
+    let rows = vec![
+        vec!["Received".to_string(), telemetry.len().to_string()],
+        vec![
+            "PACKET_COUNT range".to_string(),
+            format!(
+                "{} - {}",
+                telemetry[0].packet_count,
+                telemetry[telemetry.len() - 1].packet_count
+            ),
+        ],
+        vec!["Missing".to_string(), missing.to_string()],
+        vec!["Duplicated".to_string(), duplicated.to_string()],
+        vec!["Counter resets".to_string(), resets.to_string()],
+        vec!["Flight mode packets".to_string(), flight_mode.to_string()],
+        vec![
+            "Simulation mode packets".to_string(),
+            simulation_mode.to_string(),
+        ],
+    ];
+
+    Block::Table {
+        headers: vec!["Packets".to_string(), "Count".to_string()],
+        rows,
+    }
+}
+
+fn sensor_statistics(telemetry: &[Telemetry]) -> Block {
+    let rows = SENSORS
+        .iter()
+        .filter_map(|(name, unit, value)| {
+            let stats = series_stats(telemetry.iter().map(value))?;
+            Some(vec![
+                name.to_string(),
+                unit.to_string(),
+                format!("{:.2}", stats.min),
+                format!("{:.2}", stats.max),
+                format!("{:.2}", stats.mean),
+            ])
+        })
+        .collect();
+
+    Block::Table {
+        headers: ["Sensor", "Unit", "Min", "Max", "Mean"]
+            .iter()
+            .map(|header| header.to_string())
+            .collect(),
+        rows,
+    }
+}
+
+fn gps_extent(telemetry: &[Telemetry]) -> Block {
+    let fixes: Vec<&Telemetry> = telemetry.iter().filter(|t| t.has_gps_fix()).collect();
+    if fixes.is_empty() {
+        return Block::Paragraph("No GPS fix was received.".to_string());
+    }
+
+    let latitude = series_stats(fixes.iter().map(|t| t.gps_latitude as f64)).unwrap();
+    let longitude = series_stats(fixes.iter().map(|t| t.gps_longitude as f64)).unwrap();
+    let altitude = series_stats(fixes.iter().map(|t| t.gps_altitude as f64)).unwrap();
+    let origin = fixes[0];
+    let max_distance = fixes
+        .iter()
+        .map(|t| {
+            haversine_m(
+                origin.gps_latitude as f64,
+                origin.gps_longitude as f64,
+                t.gps_latitude as f64,
+                t.gps_longitude as f64,
+            )
+        })
+        .fold(0.0, f64::max);
+    let last = fixes[fixes.len() - 1];
+
+    let rows = vec![
+        vec!["Fixes".to_string(), fixes.len().to_string()],
+        vec![
+            "Latitude".to_string(),
+            format!("{:.4} - {:.4}", latitude.min, latitude.max),
+        ],
+        vec![
+            "Longitude".to_string(),
+            format!("{:.4} - {:.4}", longitude.min, longitude.max),
+        ],
+        vec![
+            "GPS altitude (m)".to_string(),
+            format!("{:.1} - {:.1}", altitude.min, altitude.max),
+        ],
+        vec![
+            "First fix".to_string(),
+            format!("{:.4}, {:.4}", origin.gps_latitude, origin.gps_longitude),
+        ],
+        vec![
+            "Last fix".to_string(),
+            format!("{:.4}, {:.4}", last.gps_latitude, last.gps_longitude),
+        ],
+        vec![
+            "Max distance from first fix (m)".to_string(),
+            format!("{:.0}", max_distance),
+        ],
+    ];
+
+    Block::Table {
+        headers: vec!["GPS".to_string(), "Value".to_string()],
+        rows,
+    }
+}
+
+fn commands_table(commands: &[SentCommand]) -> Block {
+    if commands.is_empty() {
+        return Block::Paragraph("No commands were sent.".to_string());
+    }
+    Block::Table {
+        headers: vec!["Time (UTC)".to_string(), "Command".to_string()],
+        rows: commands
+            .iter()
+            .map(|command| {
+                vec![
+                    command.time.format("%H:%M:%S").to_string(),
+                    command.command.clone(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn alarms_table(alarms: &[Alarm]) -> Block {
+    if alarms.is_empty() {
+        return Block::Paragraph("No alarms were triggered.".to_string());
+    }
+    Block::Table {
+        headers: vec![
+            "Time (UTC)".to_string(),
+            "Alarm".to_string(),
+            "Message".to_string(),
+        ],
+        rows: alarms
+            .iter()
+            .map(|alarm| {
+                vec![
+                    alarm.time.format("%H:%M:%S").to_string(),
+                    alarm.kind.clone(),
+                    alarm.message.clone(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a static line chart of `points` given as (seconds, value)
+fn line_chart_svg(title: &str, unit: &str, points: &[(f64, f64)]) -> String {
+    let x = series_stats(points.iter().map(|point| point.0));
+    let y = series_stats(points.iter().map(|point| point.1));
+    let (x, y) = match (x, y) {
+        (Some(x), Some(y)) => (x, y),
+        _ => {
+            return format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\">\
+                 <text x=\"{m}\" y=\"{m}\">{t}: no data</text></svg>",
+            w = CHART_WIDTH,
+            h = CHART_HEIGHT,
+            m = CHART_MARGIN,
+            t = escape_xml(title)
+        )
+        }
+    };
+    // Avoid dividing by zero for flat series
+    let x_span = (x.max - x.min).max(f64::EPSILON);
+    let y_span = (y.max - y.min).max(f64::EPSILON);
+    let plot_width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+
+    let mut polyline = String::new();
+    for (seconds, value) in points {
+        let px = CHART_MARGIN + (seconds - x.min) / x_span * plot_width;
+        let py = CHART_HEIGHT - CHART_MARGIN - (value - y.min) / y_span * plot_height;
+        let _ = write!(polyline, "{:.1},{:.1} ", px, py);
+    }
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"12\">\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\
+         <text x=\"{cx}\" y=\"20\" text-anchor=\"middle\" font-size=\"14\">{title} [{unit}]</text>\
+         <line x1=\"{m}\" y1=\"{bottom}\" x2=\"{right}\" y2=\"{bottom}\" stroke=\"black\"/>\
+         <line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{bottom}\" stroke=\"black\"/>\
+         <text x=\"{label_x}\" y=\"{m}\" text-anchor=\"end\">{y_max:.1}</text>\
+         <text x=\"{label_x}\" y=\"{bottom}\" text-anchor=\"end\">{y_min:.1}</text>\
+         <text x=\"{m}\" y=\"{label_y}\" text-anchor=\"start\">{x_min}</text>\
+         <text x=\"{right}\" y=\"{label_y}\" text-anchor=\"end\">{x_max}</text>\
+         <polyline fill=\"none\" stroke=\"#1f77b4\" stroke-width=\"1.5\" points=\"{points}\"/>\
+         </svg>",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+        m = CHART_MARGIN,
+        cx = CHART_WIDTH / 2.0,
+        bottom = CHART_HEIGHT - CHART_MARGIN,
+        right = CHART_WIDTH - CHART_MARGIN,
+        label_x = CHART_MARGIN - 4.0,
+        label_y = CHART_HEIGHT - CHART_MARGIN + 16.0,
+        title = escape_xml(title),
+        unit = escape_xml(unit),
+        y_min = y.min,
+        y_max = y.max,
+        x_min = format_duration(x.min),
+        x_max = format_duration(x.max),
+        points = polyline.trim_end(),
+    );
+    svg
+}
+
+fn charts(telemetry: &[Telemetry], elapsed: &[Option<f64>]) -> Vec<Block> {
+    CHARTS
+        .iter()
+        .map(|(title, unit, value)| {
+            let points: Vec<(f64, f64)> = telemetry
+                .iter()
+                .zip(elapsed)
+                .filter_map(|(t, seconds)| seconds.map(|seconds| (seconds, value(t))))
+                .collect();
+            Block::Chart {
+                slug: title.to_lowercase().replace(' ', "_"),
+                title: title.to_string(),
+                svg: line_chart_svg(title, unit, &points),
+            }
+        })
+        .collect()
+}
+
+fn build_report(
+    telemetry: &[Telemetry],
+    commands: &[SentCommand],
+    alarms: &[Alarm],
+) -> Vec<Block> {
+    let elapsed = elapsed_seconds(telemetry);
+
+    let mut blocks = vec![
+        Block::Heading("Flight summary".to_string()),
+        Block::Paragraph(format!(
+            "Generated {} UTC by the ground control station.",
+            Utc::now().format("%Y-%m-%d %H:%M:%S")
+        )),
+        overview(telemetry, &elapsed),
+        Block::Heading("State timeline".to_string()),
+        state_timeline(telemetry, &elapsed),
+        Block::Heading("Packets".to_string()),
+        packet_statistics(telemetry),
+        Block::Heading("Sensors".to_string()),
+        sensor_statistics(telemetry),
+        Block::Heading("GPS track".to_string()),
+        gps_extent(telemetry),
+        Block::Heading("Charts".to_string()),
+    ];
+    blocks.extend(charts(telemetry, &elapsed));
+    blocks.extend([
+        Block::Heading("Commands sent".to_string()),
+        commands_table(commands),
+        Block::Heading("Alarms".to_string()),
+        alarms_table(alarms),
+    ]);
+    blocks
+}
+
+/// Renders the report as Markdown, charts are returned as separate SVG files
+/// as (file name, content) because Markdown viewers do not render inline SVG
+fn render_markdown(
+    blocks: &[Block],
+    chart_prefix: &str,
+) -> (String, Vec<(String, String)>) {
+    let escape = |cell: &str| cell.replace('|', "\\|");
+    let mut markdown = String::from("# Session report\n");
+    let mut chart_files = Vec::new();
+    for block in blocks {
+        markdown.push('\n');
+        match block {
+            Block::Heading(text) => {
+                let _ = writeln!(markdown, "## {}", text);
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(markdown, "{}", text);
+            }
+            Block::Table { headers, rows } => {
+                let _ = writeln!(
+                    markdown,
+                    "| {} |",
+                    headers
+                        .iter()
+                        .map(|h| escape(h))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                );
+                let _ = writeln!(markdown, "|{}", " --- |".repeat(headers.len()));
+                for row in rows {
+                    let _ = writeln!(
+                        markdown,
+                        "| {} |",
+                        row.iter()
+                            .map(|c| escape(c))
+                            .collect::<Vec<_>>()
+                            .join(" | ")
+                    );
+                }
+            }
+            Block::Chart { slug, title, svg } => {
+                let file_name = format!("{}_{}.svg", chart_prefix, slug);
+                let _ = writeln!(markdown, "![{}]({})", title, file_name);
+                chart_files.push((file_name, svg.clone()));
+            }
+        }
+    }
+    (markdown, chart_files)
+}
+
+fn render_html(blocks: &[Block]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Session report</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #999; padding: 4px 8px; text-align: left; }\n\
+         </style>\n</head>\n<body>\n<h1>Session report</h1>\n",
+    );
+    for block in blocks {
+        match block {
+            Block::Heading(text) => {
+                let _ = writeln!(html, "<h2>{}</h2>", escape_xml(text));
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(html, "<p>{}</p>", escape_xml(text));
+            }
+            Block::Table { headers, rows } => {
+                html.push_str("<table>\n<tr>");
+                for header in headers {
+                    let _ = write!(html, "<th>{}</th>", escape_xml(header));
+                }
+                html.push_str("</tr>\n");
+                for row in rows {
+                    html.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(html, "<td>{}</td>", escape_xml(cell));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</table>\n");
+            }
+            Block::Chart { svg, .. } => {
+                let _ = writeln!(html, "<div>{}</div>", svg);
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_session_report(
+    output_file: String,
+    format: Option<String>,
+) -> Result<(), String> {
+    let format = ReportFormat::from_request(format.as_deref(), &output_file)?;
+    let telemetry = history::whole_flight().await?;
+    if telemetry.is_empty() {
+        return Err("No telemetry has been received, nothing to report.".to_string());
+    }
+    let commands = SENT_COMMANDS.lock().await.clone();
+    let alarms = ALARMS.lock().await.clone();
+
+    println!(
+        "Generating {:?} report from {} packets",
+        format,
+        telemetry.len()
+    );
+    let blocks = build_report(&telemetry, &commands, &alarms);
+
+    let output_path = Path::new(&output_file);
+    match format {
+        ReportFormat::Markdown => {
+            let chart_prefix = output_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("report");
+            let (markdown, chart_files) = render_markdown(&blocks, chart_prefix);
+            let directory = output_path.parent().unwrap_or_else(|| Path::new(""));
+            for (file_name, svg) in chart_files {
+                let chart_path = directory.join(&file_name);
+                std::fs::write(&chart_path, svg).map_err(|e| {
+                    format!("Error writing chart {:?}: {}", chart_path, e)
+                })?;
+            }
+            std::fs::write(output_path, markdown)
+                .map_err(|e| format!("Error writing report {}: {}", output_file, e))?;
+        }
+        ReportFormat::Html => {
+            std::fs::write(output_path, render_html(&blocks))
+                .map_err(|e| format!("Error writing report {}: {}", output_file, e))?;
+        }
+    }
+
+    println!("Report written to {}", output_file);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// A short flight: launch wait, ascent to 300 m, descent, with packet 4 lost and
+    /// packet 5 received twice
+    fn flight() -> Vec<Telemetry> {
+        [
+            ("12:00:00", 1, "LAUNCH_WAIT", 0.0),
+            ("12:00:01", 2, "ASCENT", 100.0),
+            ("12:00:02", 3, "ASCENT", 300.0),
+            ("12:00:03", 5, "DESCENT", 200.0),
+            ("12:00:04", 5, "DESCENT", 50.0),
+        ]
+        .into_iter()
+        .map(|(mission_time, packet_count, state, altitude)| Telemetry {
+            team_id: 1082,
+            mission_time: mission_time.to_string(),
+            packet_count,
+            mode: "F".to_string(),
+            state: state.to_string(),
+            altitude,
+            ..Default::default()
+        })
+        .collect()
+    }
+
+    /// The rows of the first table under a heading
+    fn table<'a>(blocks: &'a [Block], heading: &str) -> &'a [Vec<String>] {
+        let index = blocks
+            .iter()
+            .position(|block| matches!(block, Block::Heading(text) if text == heading))
+            .unwrap();
+        blocks[index + 1..]
+            .iter()
+            .find_map(|block| match block {
+                Block::Table { rows, .. } => Some(rows.as_slice()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    #[test]
+    fn summarises_a_flight() {
+        let commands = [SentCommand {
+            time: Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 1).unwrap(),
+            command: "CMD,1082,CX,ON".to_string(),
+        }];
+        let blocks = build_report(&flight(), &commands, &[]);
+
+        assert_eq!(
+            table(&blocks, "Flight summary"),
+            [
+                row(&["Team ID", "1082"]),
+                row(&["Mission time", "12:00:00 - 12:00:04"]),
+                row(&["Flight duration", "00:00:04"]),
+                row(&["Apogee", "300.0 m at 12:00:02 (ASCENT)"]),
+                row(&["Time to apogee", "00:00:02"]),
+            ]
+        );
+        assert_eq!(
+            table(&blocks, "State timeline"),
+            [
+                row(&[
+                    "LAUNCH_WAIT",
+                    "12:00:00",
+                    "12:00:00",
+                    "00:00:00",
+                    "1",
+                    "0.0",
+                    "0.0",
+                    "-"
+                ]),
+                row(&[
+                    "ASCENT", "12:00:01", "12:00:02", "00:00:01", "2", "100.0",
+                    "300.0", "-200.0"
+                ]),
+                row(&[
+                    "DESCENT", "12:00:03", "12:00:04", "00:00:01", "2", "200.0",
+                    "50.0", "150.0"
+                ]),
+            ]
+        );
+        let packets = table(&blocks, "Packets");
+        assert_eq!(packets[1], row(&["PACKET_COUNT range", "1 - 5"]));
+        assert_eq!(packets[2], row(&["Missing", "1"]));
+        assert_eq!(packets[3], row(&["Duplicated", "1"]));
+        assert_eq!(packets[5], row(&["Flight mode packets", "5"]));
+        assert_eq!(
+            table(&blocks, "Sensors")[0],
+            row(&["Altitude", "m", "0.00", "300.00", "130.00"])
+        );
+        assert_eq!(
+            table(&blocks, "Commands sent"),
+            [row(&["12:00:01", "CMD,1082,CX,ON"])]
+        );
+        assert!(blocks.iter().any(
+            |block| matches!(block, Block::Paragraph(text) if text == "No alarms were triggered.")
+        ));
+    }
+
+    #[test]
+    fn plots_the_altitude_against_elapsed_time() {
+        let blocks = build_report(&flight(), &[], &[]);
+        let svg = blocks
+            .iter()
+            .find_map(|block| match block {
+                Block::Chart { slug, svg, .. } if slug == "altitude" => Some(svg),
+                _ => None,
+            })
+            .unwrap();
+        // Elapsed 0..4 s across the plot width, 0..300 m up its height
+        assert!(svg.contains(
+            "points=\"48.0,212.0 204.0,157.3 360.0,48.0 516.0,102.7 672.0,184.7\""
+        ));
+        assert!(svg.contains(">300.0</text>"));
+        assert!(svg.contains(">00:00:04</text>"));
+
+        let (markdown, charts) = render_markdown(&blocks, "flight");
+        assert_eq!(charts.len(), CHARTS.len());
+        assert!(markdown.contains("![Altitude](flight_altitude.svg)"));
+    }
+}
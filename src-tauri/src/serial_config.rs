@@ -0,0 +1,160 @@
+use crate::transport::Framing;
+use serde::{Deserialize, Serialize};
+use serialport::{available_ports, SerialPortType};
+use tokio_serial::{SerialPort, SerialPortBuilder, SerialStream};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    #[default]
+    Eight,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopBits {
+    #[default]
+    One,
+    Two,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowControl {
+    #[default]
+    None,
+    Software,
+    Hardware,
+}
+
+/// Line settings of a connection besides the baud rate, the defaults are 8N1 without
+/// flow control like the XBee ships with
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialSettings {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    /// State of the DTR line after opening, left to the driver when not given
+    pub dtr: Option<bool>,
+    /// State of the RTS line after opening, left to the driver when not given. Has
+    /// no effect with hardware flow control, which drives RTS itself
+    pub rts: Option<bool>,
+    /// Transparent lines or the API frames of an XBee
+    pub framing: Framing,
+}
+
+impl SerialSettings {
+    pub fn configure(&self, builder: SerialPortBuilder) -> SerialPortBuilder {
+        builder
+            .data_bits(match self.data_bits {
+                DataBits::Five => tokio_serial::DataBits::Five,
+                DataBits::Six => tokio_serial::DataBits::Six,
+                DataBits::Seven => tokio_serial::DataBits::Seven,
+                DataBits::Eight => tokio_serial::DataBits::Eight,
+            })
+            .parity(match self.parity {
+                Parity::None => tokio_serial::Parity::None,
+                Parity::Odd => tokio_serial::Parity::Odd,
+                Parity::Even => tokio_serial::Parity::Even,
+            })
+            .stop_bits(match self.stop_bits {
+                StopBits::One => tokio_serial::StopBits::One,
+                StopBits::Two => tokio_serial::StopBits::Two,
+            })
+            .flow_control(match self.flow_control {
+                FlowControl::None => tokio_serial::FlowControl::None,
+                FlowControl::Software => tokio_serial::FlowControl::Software,
+                FlowControl::Hardware => tokio_serial::FlowControl::Hardware,
+            })
+    }
+
+    /// Sets the modem control lines of an opened port
+    pub fn apply_control_lines(&self, stream: &mut SerialStream) -> Result<(), String> {
+        if let Some(dtr) = self.dtr {
+            stream
+                .write_data_terminal_ready(dtr)
+                .map_err(|e| format!("Error setting DTR: {}", e))?;
+        }
+        if let Some(rts) = self.rts {
+            stream
+                .write_request_to_send(rts)
+                .map_err(|e| format!("Error setting RTS: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PortType {
+    Usb,
+    Pci,
+    Bluetooth,
+    Unknown,
+}
+
+/// A serial port with what the system knows about the device behind it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SerialPortInfo {
+    pub name: String,
+    pub port_type: PortType,
+    /// USB vendor and product ID, e.g. 0403:6015 for the FTDI chip of an XBee adapter
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    /// Serial number of the USB device, tells identical adapters apart
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+impl From<serialport::SerialPortInfo> for SerialPortInfo {
+    fn from(port: serialport::SerialPortInfo) -> Self {
+        let mut info = SerialPortInfo {
+            name: port.port_name,
+            port_type: PortType::Unknown,
+            vid: None,
+            pid: None,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+        };
+        match port.port_type {
+            SerialPortType::UsbPort(usb) => {
+                info.port_type = PortType::Usb;
+                info.vid = Some(usb.vid);
+                info.pid = Some(usb.pid);
+                info.serial_number = usb.serial_number;
+                info.manufacturer = usb.manufacturer;
+                info.product = usb.product;
+            }
+            SerialPortType::PciPort => info.port_type = PortType::Pci,
+            SerialPortType::BluetoothPort => info.port_type = PortType::Bluetooth,
+            SerialPortType::Unknown => {}
+        }
+        info
+    }
+}
+
+pub fn list_ports() -> Vec<SerialPortInfo> {
+    match available_ports() {
+        Ok(ports) => ports.into_iter().map(SerialPortInfo::from).collect(),
+        Err(e) => {
+            eprintln!("Failed to get serial ports: {}", e);
+            Vec::new()
+        }
+    }
+}
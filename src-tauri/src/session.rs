@@ -0,0 +1,500 @@
+use crate::TEAM_ID;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const MANIFEST_FILE: &str = "session.json";
+pub const TELEMETRY_LOG_FILE: &str = "telemetry.csv";
+pub const PAYLOAD_LOG_FILE: &str = "payload.csv";
+pub const LINK_QUALITY_LOG_FILE: &str = "link_quality.csv";
+pub const COMMAND_LOG_FILE: &str = "commands.log";
+pub const SIMULATION_FILE: &str = "simulation.csv";
+
+/// The manifest is rewritten every this many packets, and always when the session ends
+const MANIFEST_SAVE_INTERVAL: u64 = 10;
+
+lazy_static! {
+    static ref CURRENT_SESSION: Arc<tokio::sync::Mutex<Option<ActiveSession>>> =
+        Arc::new(tokio::sync::Mutex::new(None));
+    /// Unfinished session the next connection appends to instead of starting a new one
+    static ref RESUME_SESSION: Arc<tokio::sync::Mutex<Option<String>>> =
+        Arc::new(tokio::sync::Mutex::new(None));
+    /// Simulation file loaded last, archived in every session started after it
+    static ref SIMULATION_FILE_PATH: Arc<tokio::sync::Mutex<Option<String>>> =
+        Arc::new(tokio::sync::Mutex::new(None));
+}
+
+/// Contents of `session.json`, describing one connection to the device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionManifest {
+    /// Name of the session directory, derived from the start time
+    pub id: String,
+    /// Human readable name, set by the operator
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub team_id: i32,
+    pub port: String,
+    pub baudrate: i32,
+    pub app_version: String,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the session is running or when the app did not shut down cleanly
+    pub ended_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub packets_received: u64,
+    /// Lines that could not be parsed as telemetry or had a foreign team ID
+    #[serde(default)]
+    pub packets_rejected: u64,
+    #[serde(default)]
+    pub commands_sent: u64,
+    /// Original path of the simulation file copied into the session directory
+    #[serde(default)]
+    pub simulation_file: Option<String>,
+}
+
+struct ActiveSession {
+    directory: PathBuf,
+    manifest: SessionManifest,
+    /// Raw log of each connection by connection ID, opened with its first line
+    raw_logs: BTreeMap<String, File>,
+    command_log: File,
+}
+
+impl ActiveSession {
+    fn save_manifest(&self) -> Result<(), String> {
+        write_manifest(&self.directory, &self.manifest)
+    }
+
+    fn archive_simulation_file(&mut self, path: &str) -> Result<(), String> {
+        let target = self.directory.join(SIMULATION_FILE);
+        std::fs::copy(path, &target)
+            .map_err(|e| format!("Error copying {} to {:?}: {}", path, target, e))?;
+        self.manifest.simulation_file = Some(path.to_string());
+        self.save_manifest()
+    }
+
+    fn finish(&mut self) {
+        self.manifest.ended_at = Some(Utc::now());
+        if let Err(e) = self.save_manifest() {
+            eprintln!("Failed to save the session manifest: {}", e);
+        }
+        println!("Session {} ended", self.manifest.id);
+    }
+}
+
+/// Directory holding one subdirectory per session, `~/.gcs/sessions`
+pub fn sessions_root() -> Result<PathBuf, String> {
+    let mut path = dirs::home_dir().ok_or("Failed to find the home directory.")?;
+    path.push(".gcs");
+    path.push("sessions");
+    Ok(path)
+}
+
+/// Resolves the directory of a past session, rejecting anything that could escape
+/// the sessions directory
+pub fn session_directory(id: &str) -> Result<PathBuf, String> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!("Invalid session ID: {}", id));
+    }
+    let directory = sessions_root()?.join(id);
+    if !directory.join(MANIFEST_FILE).is_file() {
+        return Err(format!("Session {} does not exist.", id));
+    }
+    Ok(directory)
+}
+
+fn write_manifest(directory: &Path, manifest: &SessionManifest) -> Result<(), String> {
+    let path = directory.join(MANIFEST_FILE);
+    let json = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("Error serializing the session manifest: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Error writing {:?}: {}", path, e))
+}
+
+pub fn read_manifest(directory: &Path) -> Result<SessionManifest, String> {
+    let path = directory.join(MANIFEST_FILE);
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Error reading {:?}: {}", path, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Error parsing {:?}: {}", path, e))
+}
+
+fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Error opening file at {:?}: {}", path, e))
+}
+
+/// Creates the directory of a new session named after its start time, sessions
+/// started within the same second get a numbered suffix, e.g. `20230610_131402_UTC_2`
+fn new_session_directory(now: &DateTime<Utc>) -> Result<(String, PathBuf), String> {
+    let root = sessions_root()?;
+    std::fs::create_dir_all(&root)
+        .map_err(|e| format!("Error creating {:?}: {}", root, e))?;
+    let base = now.format("%Y%m%d_%H%M%S_UTC").to_string();
+    for suffix in 1.. {
+        let id = match suffix {
+            1 => base.clone(),
+            _ => format!("{}_{}", base, suffix),
+        };
+        let directory = root.join(&id);
+        // Fails if the directory exists, so two connections never share it
+        match std::fs::create_dir(&directory) {
+            Ok(()) => return Ok((id, directory)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Error creating {:?}: {}", directory, e)),
+        }
+    }
+    unreachable!()
+}
+
+/// Ends the running session, if any, and starts a new one for the given connection,
+/// or resumes the session selected with `resume_on_next_connection`.
+/// Returns the path the telemetry log should be appended to
+pub async fn start_session(port: &str, baudrate: i32) -> Result<PathBuf, String> {
+    let mut current = CURRENT_SESSION.lock().await;
+    if let Some(mut previous) = current.take() {
+        previous.finish();
+    }
+
+    let (directory, manifest) = match RESUME_SESSION.lock().await.take() {
+        Some(id) => {
+            let directory = session_directory(&id)?;
+            let mut manifest = read_manifest(&directory)?;
+            println!("Resuming session {}", id);
+            manifest.port = port.to_string();
+            manifest.baudrate = baudrate;
+            (directory, manifest)
+        }
+        None => {
+            let now = Utc::now();
+            let (id, directory) = new_session_directory(&now)?;
+            let manifest = SessionManifest {
+                id: id.clone(),
+                name: id,
+                tags: Vec::new(),
+                team_id: TEAM_ID,
+                port: port.to_string(),
+                baudrate,
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                started_at: now,
+                ended_at: None,
+                notes: String::new(),
+                packets_received: 0,
+                packets_rejected: 0,
+                commands_sent: 0,
+                simulation_file: None,
+            };
+            (directory, manifest)
+        }
+    };
+    write_manifest(&directory, &manifest)?;
+
+    let mut session = ActiveSession {
+        raw_logs: BTreeMap::new(),
+        command_log: open_append(&directory.join(COMMAND_LOG_FILE))?,
+        directory: directory.clone(),
+        manifest,
+    };
+    println!(
+        "Session {} started in {:?}",
+        session.manifest.id, session.directory
+    );
+    if let Some(path) = SIMULATION_FILE_PATH.lock().await.as_deref() {
+        if let Err(e) = session.archive_simulation_file(path) {
+            eprintln!("Failed to archive the simulation file: {}", e);
+        }
+    }
+    *current = Some(session);
+
+    Ok(directory.join(TELEMETRY_LOG_FILE))
+}
+
+/// Makes the next connection append to an unfinished session left by a crash
+pub async fn resume_on_next_connection(id: &str) -> Result<(), String> {
+    session_directory(id)?;
+    *RESUME_SESSION.lock().await = Some(id.to_string());
+    Ok(())
+}
+
+/// Whether the next connection resumes a recovered session instead of starting a new one
+pub async fn resume_pending() -> bool {
+    RESUME_SESSION.lock().await.is_some()
+}
+
+/// Marks a session that is not running as finished
+pub async fn finish_session(id: &str) -> Result<SessionManifest, String> {
+    let mut resume = RESUME_SESSION.lock().await;
+    if resume.as_deref() == Some(id) {
+        *resume = None;
+    }
+    drop(resume);
+    update_manifest(id, |manifest| {
+        manifest.ended_at.get_or_insert_with(Utc::now);
+    })
+    .await
+}
+
+/// Manifests of all sessions on disk, in no particular order
+fn read_all_manifests() -> Result<Vec<SessionManifest>, String> {
+    let root = sessions_root()?;
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = std::fs::read_dir(&root)
+        .map_err(|e| format!("Error reading {:?}: {}", root, e))?;
+
+    let mut sessions = Vec::new();
+    for entry in entries.flatten() {
+        if !entry.path().join(MANIFEST_FILE).is_file() {
+            continue;
+        }
+        match read_manifest(&entry.path()) {
+            Ok(manifest) => sessions.push(manifest),
+            Err(e) => eprintln!("Skipping session: {}", e),
+        }
+    }
+    Ok(sessions)
+}
+
+/// The most recent session other than the running one, if the app stopped before
+/// it was finished
+pub async fn unfinished_session() -> Result<Option<SessionManifest>, String> {
+    let running = current_session_id().await;
+    let latest = read_all_manifests()?
+        .into_iter()
+        .filter(|manifest| Some(&manifest.id) != running.as_ref())
+        .max_by_key(|manifest| manifest.started_at);
+    Ok(latest.filter(|manifest| manifest.ended_at.is_none()))
+}
+
+pub async fn current_session_id() -> Option<String> {
+    CURRENT_SESSION
+        .lock()
+        .await
+        .as_ref()
+        .map(|session| session.manifest.id.clone())
+}
+
+/// The telemetry log of the running session, for a connection that joins it
+pub async fn current_telemetry_log() -> Option<PathBuf> {
+    CURRENT_SESSION
+        .lock()
+        .await
+        .as_ref()
+        .map(|session| session.directory.join(TELEMETRY_LOG_FILE))
+}
+
+/// Name of the raw log of a connection, e.g. `raw_primary.log`
+pub fn raw_log_file(connection_id: &str) -> String {
+    let id: String = connection_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("raw_{}.log", id)
+}
+
+/// Appends a line exactly as received from the device to the raw log of the
+/// connection it arrived on
+pub async fn log_raw_line(connection_id: &str, line: &str) {
+    if let Some(session) = CURRENT_SESSION.lock().await.as_mut() {
+        let raw_log = match session.raw_logs.get_mut(connection_id) {
+            Some(raw_log) => raw_log,
+            None => {
+                let path = session.directory.join(raw_log_file(connection_id));
+                match open_append(&path) {
+                    Ok(raw_log) => session
+                        .raw_logs
+                        .entry(connection_id.to_string())
+                        .or_insert(raw_log),
+                    Err(e) => {
+                        eprintln!("Failed to open the raw log: {}", e);
+                        return;
+                    }
+                }
+            }
+        };
+        let _ = writeln!(
+            raw_log,
+            "{}\t{}",
+            Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            line
+        );
+    }
+}
+
+pub async fn log_command(command: &str) {
+    if let Some(session) = CURRENT_SESSION.lock().await.as_mut() {
+        session.manifest.commands_sent += 1;
+        let _ = writeln!(
+            session.command_log,
+            "{}\t{}",
+            Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            command
+        );
+    }
+}
+
+/// Counts a packet in the manifest, `accepted` is false for rejected lines
+pub async fn count_packet(accepted: bool) {
+    if let Some(session) = CURRENT_SESSION.lock().await.as_mut() {
+        if accepted {
+            session.manifest.packets_received += 1;
+        } else {
+            session.manifest.packets_rejected += 1;
+        }
+        let total =
+            session.manifest.packets_received + session.manifest.packets_rejected;
+        if total % MANIFEST_SAVE_INTERVAL == 0 {
+            if let Err(e) = session.save_manifest() {
+                eprintln!("Failed to save the session manifest: {}", e);
+            }
+        }
+    }
+}
+
+/// Copies the simulation file into the running session directory. Without a running
+/// session it is copied into the next one when it starts
+pub async fn attach_simulation_file(path: &str) -> Result<(), String> {
+    *SIMULATION_FILE_PATH.lock().await = Some(path.to_string());
+    match CURRENT_SESSION.lock().await.as_mut() {
+        Some(session) => session.archive_simulation_file(path),
+        None => {
+            println!(
+                "No session running, the simulation file is archived in the next one"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Applies a change to a session manifest, whether the session is running or not
+async fn update_manifest(
+    id: &str,
+    update: impl FnOnce(&mut SessionManifest),
+) -> Result<SessionManifest, String> {
+    let mut current = CURRENT_SESSION.lock().await;
+    if let Some(session) = current.as_mut().filter(|session| session.manifest.id == id)
+    {
+        update(&mut session.manifest);
+        session.save_manifest()?;
+        return Ok(session.manifest.clone());
+    }
+    let directory = session_directory(id)?;
+    let mut manifest = read_manifest(&directory)?;
+    update(&mut manifest);
+    write_manifest(&directory, &manifest)?;
+    Ok(manifest)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_sessions() -> Result<Vec<SessionManifest>, String> {
+    let mut sessions = read_all_manifests()?;
+
+    // The running session is more up to date in memory than on disk
+    if let Some(session) = CURRENT_SESSION.lock().await.as_ref() {
+        for manifest in sessions.iter_mut() {
+            if manifest.id == session.manifest.id {
+                *manifest = session.manifest.clone();
+            }
+        }
+    }
+
+    sessions.sort_by_key(|manifest| std::cmp::Reverse(manifest.started_at));
+    Ok(sessions)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_current_session() -> Option<SessionManifest> {
+    CURRENT_SESSION
+        .lock()
+        .await
+        .as_ref()
+        .map(|session| session.manifest.clone())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn rename_session(
+    id: String,
+    name: String,
+) -> Result<SessionManifest, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Session name cannot be empty.".to_string());
+    }
+    update_manifest(&id, |manifest| manifest.name = name).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn tag_session(
+    id: String,
+    tags: Vec<String>,
+) -> Result<SessionManifest, String> {
+    let mut tags: Vec<String> = tags
+        .into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    update_manifest(&id, |manifest| manifest.tags = tags).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_session_notes(
+    id: String,
+    notes: String,
+) -> Result<SessionManifest, String> {
+    update_manifest(&id, |manifest| manifest.notes = notes).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_session(id: String) -> Result<(), String> {
+    if let Some(session) = CURRENT_SESSION.lock().await.as_ref() {
+        if session.manifest.id == id {
+            return Err("Cannot delete the running session.".to_string());
+        }
+    }
+    let directory = session_directory(&id)?;
+    std::fs::remove_dir_all(&directory)
+        .map_err(|e| format!("Error deleting {:?}: {}", directory, e))?;
+    println!("Deleted session {}", id);
+    Ok(())
+}
+
+/// Ends the running session, returns false if none is running
+pub async fn finish_current_session() -> bool {
+    match CURRENT_SESSION.lock().await.take() {
+        Some(mut session) => {
+            session.finish();
+            true
+        }
+        None => false,
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn end_session() -> Result<(), String> {
+    if finish_current_session().await {
+        Ok(())
+    } else {
+        Err("No session is running.".to_string())
+    }
+}
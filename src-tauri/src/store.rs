@@ -0,0 +1,710 @@
+use crate::events::{Alarm, SentCommand};
+use crate::packet_types::PayloadTelemetry;
+use crate::session;
+use crate::Telemetry;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+const DATABASE_FILE: &str = "telemetry.sqlite3";
+
+/// Telemetry columns that can be queried as a numeric time series
+const NUMERIC_FIELDS: [&str; 12] = [
+    "packet_count",
+    "altitude",
+    "temperature",
+    "pressure",
+    "voltage",
+    "gps_altitude",
+    "gps_latitude",
+    "gps_longitude",
+    "gps_sats",
+    "tilt_x",
+    "tilt_y",
+    "received_monotonic_s",
+];
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS telemetry (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT,
+        received_utc TEXT,
+        received_monotonic_s REAL,
+        rssi_dbm INTEGER,
+        team_id INTEGER NOT NULL,
+        mission_time TEXT NOT NULL,
+        packet_count INTEGER NOT NULL,
+        mode TEXT NOT NULL,
+        state TEXT NOT NULL,
+        altitude REAL NOT NULL,
+        hs_deployed TEXT NOT NULL,
+        pc_deployed TEXT NOT NULL,
+        mast_raised TEXT NOT NULL,
+        temperature REAL NOT NULL,
+        pressure REAL NOT NULL,
+        voltage REAL NOT NULL,
+        gps_time TEXT NOT NULL,
+        gps_altitude REAL NOT NULL,
+        gps_latitude REAL NOT NULL,
+        gps_longitude REAL NOT NULL,
+        gps_sats INTEGER NOT NULL,
+        tilt_x REAL NOT NULL,
+        tilt_y REAL NOT NULL,
+        cmd_echo TEXT NOT NULL,
+        extra TEXT
+    );
+    CREATE INDEX IF NOT EXISTS telemetry_session_time
+        ON telemetry (session_id, received_utc);
+    CREATE INDEX IF NOT EXISTS telemetry_state ON telemetry (state);
+
+    CREATE TABLE IF NOT EXISTS payload_telemetry (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT,
+        received_utc TEXT,
+        received_monotonic_s REAL,
+        rssi_dbm INTEGER,
+        team_id INTEGER NOT NULL,
+        mission_time TEXT NOT NULL,
+        packet_count INTEGER NOT NULL,
+        packet_type TEXT NOT NULL,
+        altitude REAL NOT NULL,
+        temperature REAL NOT NULL,
+        voltage REAL NOT NULL,
+        gyro_r REAL NOT NULL,
+        gyro_p REAL NOT NULL,
+        gyro_y REAL NOT NULL,
+        accel_r REAL NOT NULL,
+        accel_p REAL NOT NULL,
+        accel_y REAL NOT NULL,
+        mag_r REAL NOT NULL,
+        mag_p REAL NOT NULL,
+        mag_y REAL NOT NULL,
+        pointing_error REAL NOT NULL,
+        state TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS payload_telemetry_session_time
+        ON payload_telemetry (session_id, received_utc);
+
+    CREATE TABLE IF NOT EXISTS raw_lines (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT,
+        received_utc TEXT NOT NULL,
+        line TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS raw_lines_session_time
+        ON raw_lines (session_id, received_utc);
+
+    CREATE TABLE IF NOT EXISTS commands (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT,
+        sent_utc TEXT NOT NULL,
+        command TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        session_id TEXT,
+        time_utc TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        message TEXT NOT NULL
+    );
+";
+
+/// Columns added to the telemetry table since its first version, with their types
+const ADDED_COLUMNS: [(&str, &str); 2] = [("rssi_dbm", "INTEGER"), ("extra", "TEXT")];
+
+/// An insert queued for the writer thread, with what it stores for the error message
+type Write = (
+    &'static str,
+    Box<dyn FnOnce(&Connection) -> rusqlite::Result<usize> + Send>,
+);
+
+lazy_static! {
+    /// Locked by blocking code only, the writer thread and the queries run in
+    /// `spawn_blocking`
+    static ref DATABASE: Arc<Mutex<Option<Connection>>> = Arc::new(Mutex::new(None));
+    static ref WRITES: mpsc::Sender<Write> = spawn_writer();
+}
+
+fn database_path() -> Result<PathBuf, String> {
+    let mut path = dirs::home_dir().ok_or("Failed to find the home directory.")?;
+    path.push(".gcs");
+    std::fs::create_dir_all(&path)
+        .map_err(|e| format!("Error creating {:?}: {}", path, e))?;
+    path.push(DATABASE_FILE);
+    Ok(path)
+}
+
+fn open_database() -> Result<Connection, String> {
+    let path = database_path()?;
+    let connection = Connection::open(&path)
+        .map_err(|e| format!("Error opening the database at {:?}: {}", path, e))?;
+    // WAL keeps every committed packet on disk even if the app crashes, without
+    // paying for a full sync on every insert
+    connection
+        .execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+        .map_err(|e| format!("Error configuring the database: {}", e))?;
+    connection
+        .execute_batch(SCHEMA)
+        .map_err(|e| format!("Error creating the database schema: {}", e))?;
+    add_missing_columns(&connection)
+        .map_err(|e| format!("Error updating the database schema: {}", e))?;
+    println!("Opened telemetry database at {:?}", path);
+    Ok(connection)
+}
+
+/// Databases created by older versions lack the columns added since, `CREATE TABLE IF
+/// NOT EXISTS` leaves their tables as they are
+fn add_missing_columns(connection: &Connection) -> rusqlite::Result<()> {
+    let mut statement = connection.prepare("PRAGMA table_info(telemetry)")?;
+    let columns = statement
+        .query_map([], |row| row.get::<_, String>("name"))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    for (name, kind) in ADDED_COLUMNS {
+        if !columns.iter().any(|column| column == name) {
+            connection.execute_batch(&format!(
+                "ALTER TABLE telemetry ADD COLUMN {} {}",
+                name, kind
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `f` on the database connection, opening it on first use. Blocks, call it from
+/// the writer thread or `spawn_blocking` only
+fn with_database_blocking<T>(
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let mut database = DATABASE
+        .lock()
+        .map_err(|_| "The database connection was poisoned".to_string())?;
+    if database.is_none() {
+        *database = Some(open_database()?);
+    }
+    let connection = database.as_ref().unwrap();
+    f(connection).map_err(|e| format!("Database error: {}", e))
+}
+
+/// Runs `f` on the database connection without blocking the async runtime
+async fn with_database<T: Send + 'static>(
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(move || with_database_blocking(f))
+        .await
+        .map_err(|e| format!("The database query failed: {}", e))?
+}
+
+/// Starts the thread that performs the inserts in the order they were queued
+fn spawn_writer() -> mpsc::Sender<Write> {
+    let (sender, receiver) = mpsc::channel::<Write>();
+    std::thread::spawn(move || {
+        for (what, f) in receiver {
+            if let Err(e) = with_database_blocking(f) {
+                eprintln!("Failed to store {}: {}", what, e);
+            }
+        }
+    });
+    sender
+}
+
+/// Queues an insert for the writer thread. Writes are best effort, a database problem
+/// must never stop the reception, and the reception never waits for the disk
+fn write(
+    what: &'static str,
+    f: impl FnOnce(&Connection) -> rusqlite::Result<usize> + Send + 'static,
+) {
+    if WRITES.send((what, Box::new(f))).is_err() {
+        eprintln!("Failed to store {}: the database writer stopped", what);
+    }
+}
+
+/// Schema fields without a column of their own are kept together as a JSON object
+fn extra_to_json(t: &Telemetry) -> Option<String> {
+    if t.extra.is_empty() {
+        None
+    } else {
+        serde_json::to_string(&t.extra).ok()
+    }
+}
+
+fn insert_telemetry_row(
+    connection: &Connection,
+    session_id: Option<&str>,
+    t: &Telemetry,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        "INSERT INTO telemetry (
+            session_id, received_utc, received_monotonic_s, rssi_dbm, team_id,
+            mission_time, packet_count, mode, state, altitude, hs_deployed,
+            pc_deployed, mast_raised, temperature, pressure, voltage, gps_time,
+            gps_altitude, gps_latitude, gps_longitude, gps_sats, tilt_x, tilt_y,
+            cmd_echo, extra
+        ) VALUES (
+            ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+            ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25
+        )",
+        params![
+            session_id,
+            t.received_utc,
+            t.received_monotonic_s,
+            t.rssi_dbm,
+            t.team_id,
+            t.mission_time,
+            t.packet_count,
+            t.mode,
+            t.state,
+            t.altitude,
+            t.hs_deployed,
+            t.pc_deployed,
+            t.mast_raised,
+            t.temperature,
+            t.pressure,
+            t.voltage,
+            t.gps_time,
+            t.gps_altitude,
+            t.gps_latitude,
+            t.gps_longitude,
+            t.gps_sats,
+            t.tilt_x,
+            t.tilt_y,
+            t.cmd_echo,
+            extra_to_json(t),
+        ],
+    )
+}
+
+pub async fn insert_telemetry(t: &Telemetry) {
+    let session_id = session::current_session_id().await;
+    let t = t.clone();
+    write("telemetry", move |connection| {
+        insert_telemetry_row(connection, session_id.as_deref(), &t)
+    })
+}
+
+pub async fn insert_payload(p: &PayloadTelemetry) {
+    let session_id = session::current_session_id().await;
+    let p = p.clone();
+    write("payload telemetry", move |connection| {
+        connection.execute(
+            "INSERT INTO payload_telemetry (
+                session_id, received_utc, received_monotonic_s, rssi_dbm, team_id,
+                mission_time, packet_count, packet_type, altitude, temperature, voltage,
+                gyro_r, gyro_p, gyro_y, accel_r, accel_p, accel_y, mag_r, mag_p, mag_y,
+                pointing_error, state
+            ) VALUES (
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                ?17, ?18, ?19, ?20, ?21, ?22
+            )",
+            params![
+                session_id,
+                p.received_utc,
+                p.received_monotonic_s,
+                p.rssi_dbm,
+                p.team_id,
+                p.mission_time,
+                p.packet_count,
+                p.packet_type,
+                p.altitude,
+                p.temperature,
+                p.voltage,
+                p.gyro_r,
+                p.gyro_p,
+                p.gyro_y,
+                p.accel_r,
+                p.accel_p,
+                p.accel_y,
+                p.mag_r,
+                p.mag_p,
+                p.mag_y,
+                p.pointing_error,
+                p.state,
+            ],
+        )
+    })
+}
+
+pub async fn insert_raw_line(line: &str) {
+    let session_id = session::current_session_id().await;
+    let line = line.to_string();
+    let received_utc = Utc::now();
+    write("raw line", move |connection| {
+        connection.execute(
+            "INSERT INTO raw_lines (session_id, received_utc, line) VALUES (?1, ?2, ?3)",
+            params![session_id, received_utc, line],
+        )
+    })
+}
+
+pub async fn insert_command(command: &SentCommand) {
+    let session_id = session::current_session_id().await;
+    let command = command.clone();
+    write("command", move |connection| {
+        connection.execute(
+            "INSERT INTO commands (session_id, sent_utc, command) VALUES (?1, ?2, ?3)",
+            params![session_id, command.time, command.command],
+        )
+    })
+}
+
+pub async fn insert_event(alarm: &Alarm) {
+    let session_id = session::current_session_id().await;
+    let alarm = alarm.clone();
+    write("event", move |connection| {
+        connection.execute(
+            "INSERT INTO events (session_id, time_utc, kind, message)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![session_id, alarm.time, alarm.kind, alarm.message],
+        )
+    })
+}
+
+fn telemetry_from_row(row: &Row) -> rusqlite::Result<Telemetry> {
+    Ok(Telemetry {
+        team_id: row.get("team_id")?,
+        mission_time: row.get("mission_time")?,
+        packet_count: row.get("packet_count")?,
+        mode: row.get("mode")?,
+        state: row.get("state")?,
+        altitude: row.get("altitude")?,
+        hs_deployed: row.get("hs_deployed")?,
+        pc_deployed: row.get("pc_deployed")?,
+        mast_raised: row.get("mast_raised")?,
+        temperature: row.get("temperature")?,
+        pressure: row.get("pressure")?,
+        voltage: row.get("voltage")?,
+        gps_time: row.get("gps_time")?,
+        gps_altitude: row.get("gps_altitude")?,
+        gps_latitude: row.get("gps_latitude")?,
+        gps_longitude: row.get("gps_longitude")?,
+        gps_sats: row.get("gps_sats")?,
+        tilt_x: row.get("tilt_x")?,
+        tilt_y: row.get("tilt_y")?,
+        cmd_echo: row.get("cmd_echo")?,
+        received_utc: row.get("received_utc")?,
+        received_monotonic_s: row.get("received_monotonic_s")?,
+        rssi_dbm: row.get("rssi_dbm")?,
+        extra: match row.get::<_, Option<String>>("extra")? {
+            Some(extra) => serde_json::from_str(&extra).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    row.as_ref().column_index("extra").unwrap_or_default(),
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?,
+            None => Default::default(),
+        },
+    })
+}
+
+fn payload_from_row(row: &Row) -> rusqlite::Result<PayloadTelemetry> {
+    Ok(PayloadTelemetry {
+        team_id: row.get("team_id")?,
+        mission_time: row.get("mission_time")?,
+        packet_count: row.get("packet_count")?,
+        packet_type: row.get("packet_type")?,
+        altitude: row.get("altitude")?,
+        temperature: row.get("temperature")?,
+        voltage: row.get("voltage")?,
+        gyro_r: row.get("gyro_r")?,
+        gyro_p: row.get("gyro_p")?,
+        gyro_y: row.get("gyro_y")?,
+        accel_r: row.get("accel_r")?,
+        accel_p: row.get("accel_p")?,
+        accel_y: row.get("accel_y")?,
+        mag_r: row.get("mag_r")?,
+        mag_p: row.get("mag_p")?,
+        mag_y: row.get("mag_y")?,
+        pointing_error: row.get("pointing_error")?,
+        state: row.get("state")?,
+        received_utc: row.get("received_utc")?,
+        received_monotonic_s: row.get("received_monotonic_s")?,
+        rssi_dbm: row.get("rssi_dbm")?,
+    })
+}
+
+/// Filters shared by the query commands, every one of them is optional
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TelemetryFilter {
+    pub session_id: Option<String>,
+    /// Inclusive lower bound of the receive time
+    pub from_utc: Option<DateTime<Utc>>,
+    /// Inclusive upper bound of the receive time
+    pub to_utc: Option<DateTime<Utc>>,
+    pub state: Option<String>,
+    /// Maximum number of rows returned, the oldest ones are returned first
+    pub limit: Option<u32>,
+}
+
+impl TelemetryFilter {
+    /// Builds the WHERE and LIMIT clauses together with their parameters
+    fn to_sql(&self, time_column: &str) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(session_id) = &self.session_id {
+            values.push(Value::Text(session_id.clone()));
+            conditions.push(format!("session_id = ?{}", values.len()));
+        }
+        if let Some(from_utc) = self.from_utc {
+            values.push(Value::Text(from_utc.format("%F %T%.f%:z").to_string()));
+            conditions.push(format!("{} >= ?{}", time_column, values.len()));
+        }
+        if let Some(to_utc) = self.to_utc {
+            values.push(Value::Text(to_utc.format("%F %T%.f%:z").to_string()));
+            conditions.push(format!("{} <= ?{}", time_column, values.len()));
+        }
+        if let Some(state) = &self.state {
+            values.push(Value::Text(state.clone()));
+            conditions.push(format!("state = ?{}", values.len()));
+        }
+
+        let mut sql = String::new();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY id");
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        (sql, values)
+    }
+}
+
+/// One point of a numeric telemetry field over time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeriesPoint {
+    pub received_utc: Option<DateTime<Utc>>,
+    pub mission_time: String,
+    pub value: f64,
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn query_telemetry(
+    filter: TelemetryFilter,
+) -> Result<Vec<Telemetry>, String> {
+    let (clauses, values) = filter.to_sql("received_utc");
+    let sql = format!("SELECT * FROM telemetry{}", clauses);
+    with_database(move |connection| {
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), telemetry_from_row)?;
+        rows.collect()
+    })
+    .await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn query_payload_telemetry(
+    filter: TelemetryFilter,
+) -> Result<Vec<PayloadTelemetry>, String> {
+    let (clauses, values) = filter.to_sql("received_utc");
+    let sql = format!("SELECT * FROM payload_telemetry{}", clauses);
+    with_database(move |connection| {
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), payload_from_row)?;
+        rows.collect()
+    })
+    .await
+}
+
+/// The query of `query_telemetry_series` with its parameters
+fn series_sql(
+    field: &str,
+    filter: &TelemetryFilter,
+) -> Result<(String, Vec<Value>), String> {
+    // The field name ends up in the SQL, so only known columns are accepted
+    if !NUMERIC_FIELDS.contains(&field) {
+        return Err(format!("Unknown telemetry field: {}", field));
+    }
+    let (clauses, values) = filter.to_sql("received_utc");
+    let sql = format!(
+        "SELECT received_utc, mission_time, {} FROM telemetry{}",
+        field, clauses
+    );
+    Ok((sql, values))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn query_telemetry_series(
+    field: String,
+    filter: TelemetryFilter,
+) -> Result<Vec<SeriesPoint>, String> {
+    let (sql, values) = series_sql(&field, &filter)?;
+    with_database(move |connection| {
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok(SeriesPoint {
+                received_utc: row.get(0)?,
+                mission_time: row.get(1)?,
+                value: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    })
+    .await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn query_commands(
+    filter: TelemetryFilter,
+) -> Result<Vec<SentCommand>, String> {
+    let filter = TelemetryFilter {
+        state: None,
+        ..filter
+    };
+    let (clauses, values) = filter.to_sql("sent_utc");
+    let sql = format!("SELECT sent_utc, command FROM commands{}", clauses);
+    with_database(move |connection| {
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok(SentCommand {
+                time: row.get(0)?,
+                command: row.get(1)?,
+            })
+        })?;
+        rows.collect()
+    })
+    .await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn query_events(filter: TelemetryFilter) -> Result<Vec<Alarm>, String> {
+    let filter = TelemetryFilter {
+        state: None,
+        ..filter
+    };
+    let (clauses, values) = filter.to_sql("time_utc");
+    let sql = format!("SELECT time_utc, kind, message FROM events{}", clauses);
+    with_database(move |connection| {
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok(Alarm {
+                time: row.get(0)?,
+                kind: row.get(1)?,
+                message: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(connection: &Connection) -> Vec<String> {
+        let mut statement = connection.prepare("PRAGMA table_info(telemetry)").unwrap();
+        let columns = statement
+            .query_map([], |row| row.get::<_, String>("name"))
+            .unwrap();
+        columns.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn adds_the_new_columns_to_an_old_database() {
+        let connection = Connection::open_in_memory().unwrap();
+        // The telemetry table as the first version created it
+        let old_schema = SCHEMA
+            .replace(
+                "        rssi_dbm INTEGER,\n        team_id",
+                "        team_id",
+            )
+            .replace(
+                "cmd_echo TEXT NOT NULL,\n        extra TEXT",
+                "cmd_echo TEXT NOT NULL",
+            );
+        connection.execute_batch(&old_schema).unwrap();
+        assert!(!columns(&connection).contains(&"rssi_dbm".to_string()));
+        assert!(!columns(&connection).contains(&"extra".to_string()));
+
+        add_missing_columns(&connection).unwrap();
+        // Running it again on an up to date database changes nothing
+        add_missing_columns(&connection).unwrap();
+        let columns = columns(&connection);
+        for (name, _) in ADDED_COLUMNS {
+            assert_eq!(columns.iter().filter(|column| *column == name).count(), 1);
+        }
+    }
+
+    #[test]
+    fn restores_the_extra_fields_of_a_packet() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        let mut packet = Telemetry {
+            team_id: 1082,
+            mission_time: "12:00:01".to_string(),
+            packet_count: 7,
+            rssi_dbm: Some(-61),
+            ..Default::default()
+        };
+        packet
+            .extra
+            .insert("co2_ppm".to_string(), serde_json::json!(412.5));
+        insert_telemetry_row(&connection, Some("session"), &packet).unwrap();
+        insert_telemetry_row(&connection, None, &Telemetry::default()).unwrap();
+
+        let mut statement = connection
+            .prepare("SELECT * FROM telemetry ORDER BY id")
+            .unwrap();
+        let rows: Vec<Telemetry> = statement
+            .query_map([], telemetry_from_row)
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows[0].packet_count, 7);
+        assert_eq!(rows[0].rssi_dbm, Some(-61));
+        assert_eq!(rows[0].extra, packet.extra);
+        assert!(rows[1].extra.is_empty());
+    }
+
+    #[test]
+    fn builds_the_filter_clauses_with_numbered_parameters() {
+        let filter = TelemetryFilter {
+            session_id: Some("session".to_string()),
+            from_utc: None,
+            to_utc: Some("2026-10-18T12:00:00Z".parse().unwrap()),
+            state: Some("DESCENT".to_string()),
+            limit: Some(10),
+        };
+        let (sql, values) = filter.to_sql("sent_utc");
+        assert_eq!(
+            sql,
+            " WHERE session_id = ?1 AND sent_utc <= ?2 AND state = ?3 ORDER BY id LIMIT 10"
+        );
+        assert_eq!(
+            values,
+            vec![
+                Value::Text("session".to_string()),
+                Value::Text("2026-10-18 12:00:00+00:00".to_string()),
+                Value::Text("DESCENT".to_string()),
+            ]
+        );
+        assert_eq!(
+            TelemetryFilter::default().to_sql("sent_utc").0,
+            " ORDER BY id"
+        );
+    }
+
+    #[test]
+    fn queries_only_the_numeric_columns_as_series() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        for field in NUMERIC_FIELDS {
+            let (sql, _) = series_sql(field, &TelemetryFilter::default()).unwrap();
+            connection.prepare(&sql).unwrap();
+        }
+        let filter = TelemetryFilter::default();
+        assert!(series_sql("mode", &filter).is_err());
+        assert!(series_sql(
+            "altitude FROM telemetry; DROP TABLE telemetry; --",
+            &filter
+        )
+        .is_err());
+    }
+}
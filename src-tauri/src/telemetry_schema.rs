@@ -0,0 +1,543 @@
+use crate::units::{Conversion, DisplayUnits, DISPLAY_UNITS};
+use crate::Telemetry;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Schema file in `~/.gcs` that replaces the built-in schema
+pub const SCHEMA_FILE: &str = "telemetry_schema.json";
+/// The competition format the ground station was written for
+const DEFAULT_SCHEMA: &str = include_str!("../telemetry_schema.json");
+/// Fields without which a packet cannot be handled at all
+const REQUIRED_FIELDS: [&str; 2] = ["team_id", "mission_time"];
+
+/// Columns the ground station adds to its own flight logs after the schema fields
+const RECEIVED_UTC_COLUMN: &str = "received_utc";
+const RECEIVED_MONOTONIC_COLUMN: &str = "received_monotonic_s";
+const RSSI_COLUMN: &str = "rssi_dbm";
+
+lazy_static! {
+    pub static ref TELEMETRY_SCHEMA: Arc<tokio::sync::Mutex<TelemetrySchema>> =
+        Arc::new(tokio::sync::Mutex::new(TelemetrySchema::load()));
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Integer,
+    Float,
+    Text,
+    /// A time of day as hh:mm:ss, kept as text
+    Time,
+}
+
+impl FieldType {
+    /// The name of the type in the schema file
+    fn name(self) -> &'static str {
+        match self {
+            FieldType::Integer => "integer",
+            FieldType::Float => "float",
+            FieldType::Text => "text",
+            FieldType::Time => "time",
+        }
+    }
+}
+
+/// The types a field of `Telemetry` can be read from, `None` for fields it does not
+/// know, which go to its `extra` map as they are
+fn telemetry_field_types(name: &str) -> Option<&'static [FieldType]> {
+    match name {
+        "team_id" | "packet_count" | "gps_sats" => Some(&[FieldType::Integer]),
+        "altitude" | "temperature" | "pressure" | "voltage" | "gps_altitude"
+        | "gps_latitude" | "gps_longitude" | "tilt_x" | "tilt_y" => {
+            Some(&[FieldType::Float, FieldType::Integer])
+        }
+        "mission_time" | "mode" | "state" | "hs_deployed" | "pc_deployed"
+        | "mast_raised" | "gps_time" | "cmd_echo" => {
+            Some(&[FieldType::Text, FieldType::Time])
+        }
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FieldDefinition {
+    /// Name of the field in `Telemetry` and the events sent to the UI. Fields that
+    /// `Telemetry` does not know are kept in its `extra` map under this name
+    pub name: String,
+    /// Column header in the competition CSV, e.g. ALTITUDE
+    pub csv_name: String,
+    /// Label for the UI, the name when left out
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Smallest step of the value, decides the decimals written to CSV, e.g. 0.1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<f64>,
+    /// Range of plausible values, values outside of it are most likely corrupted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl FieldDefinition {
+    fn parse(&self, raw: &str) -> Result<Value, String> {
+        let invalid = || format!("Invalid {}: {:?}", self.name, raw);
+        match self.field_type {
+            FieldType::Integer => {
+                raw.parse::<i64>().map(Value::from).map_err(|_| invalid())
+            }
+            FieldType::Float => {
+                let value = raw.parse::<f64>().map_err(|_| invalid())?;
+                serde_json::Number::from_f64(value)
+                    .map(Value::Number)
+                    .ok_or_else(invalid)
+            }
+            FieldType::Text | FieldType::Time => Ok(Value::from(raw)),
+        }
+    }
+
+    fn decimals(&self) -> Option<usize> {
+        self.resolution
+            .filter(|resolution| *resolution > 0.0)
+            .map(|resolution| (-resolution.log10()).ceil().max(0.0) as usize)
+    }
+
+    fn format(&self, value: &Value) -> String {
+        match (self.field_type, value) {
+            (_, Value::Null) => String::new(),
+            (FieldType::Float, Value::Number(number)) => {
+                let number = number.as_f64().unwrap_or_default();
+                match self.decimals() {
+                    Some(decimals) => format!("{:.*}", decimals, number),
+                    None => number.to_string(),
+                }
+            }
+            (FieldType::Integer, Value::Number(number)) => match number.as_i64() {
+                Some(integer) => integer.to_string(),
+                None => format!("{:.0}", number.as_f64().unwrap_or_default()),
+            },
+            (_, Value::String(text)) => text.clone(),
+            (_, other) => other.to_string(),
+        }
+    }
+
+    fn conversion(&self, units: &DisplayUnits) -> Option<Conversion> {
+        self.unit.as_deref().and_then(|unit| units.conversion(unit))
+    }
+
+    /// The definition with unit, resolution and range in the display units
+    fn in_units(&self, units: &DisplayUnits) -> FieldDefinition {
+        match self.conversion(units) {
+            Some(conversion) => FieldDefinition {
+                unit: Some(conversion.unit.to_string()),
+                resolution: self.resolution.map(|step| conversion.apply_step(step)),
+                min: self.min.map(|min| conversion.apply(min)),
+                max: self.max.map(|max| conversion.apply(max)),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Matches both the competition header and the field name, in any case
+    fn matches_column(&self, column: &str) -> bool {
+        self.csv_name.eq_ignore_ascii_case(column)
+            || self.name.eq_ignore_ascii_case(column)
+    }
+}
+
+/// Names, types, units and CSV order of the fields of a container packet
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TelemetrySchema {
+    pub name: String,
+    pub fields: Vec<FieldDefinition>,
+}
+
+fn schema_path() -> Result<PathBuf, String> {
+    let mut path = dirs::home_dir().ok_or("Failed to find the home directory.")?;
+    path.push(".gcs");
+    path.push(SCHEMA_FILE);
+    Ok(path)
+}
+
+fn telemetry_from_fields(fields: Map<String, Value>) -> Result<Telemetry, String> {
+    serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())
+}
+
+impl TelemetrySchema {
+    fn default_schema() -> Self {
+        Self::from_json(DEFAULT_SCHEMA)
+            .expect("the built-in telemetry schema is invalid")
+    }
+
+    /// The schema in `~/.gcs`, or the built-in one if there is none or it is invalid
+    fn load() -> Self {
+        let path = match schema_path() {
+            Ok(path) if path.is_file() => path,
+            _ => return Self::default_schema(),
+        };
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Self::from_json(&json))
+        {
+            Ok(schema) => {
+                println!("Loaded telemetry schema {:?} from {:?}", schema.name, path);
+                schema
+            }
+            Err(e) => {
+                eprintln!("Ignoring the telemetry schema {:?}: {}", path, e);
+                Self::default_schema()
+            }
+        }
+    }
+
+    fn from_json(json: &str) -> Result<Self, String> {
+        let mut schema: TelemetrySchema =
+            serde_json::from_str(json).map_err(|e| format!("Invalid schema: {}", e))?;
+        schema.validate()?;
+        for field in schema.fields.iter_mut() {
+            if field.display_name.is_empty() {
+                field.display_name = field.name.clone();
+            }
+        }
+        Ok(schema)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (index, field) in self.fields.iter().enumerate() {
+            if field.name.is_empty() || field.csv_name.is_empty() {
+                return Err(format!("Field {} of the schema has no name", index + 1));
+            }
+            if self.fields[..index].iter().any(|f| f.name == field.name) {
+                return Err(format!("Field {} is defined twice", field.name));
+            }
+            if let (Some(min), Some(max)) = (field.min, field.max) {
+                if min > max {
+                    return Err(format!("The range of {} is empty", field.name));
+                }
+            }
+            if let Some(types) = telemetry_field_types(&field.name) {
+                if !types.contains(&field.field_type) {
+                    let expected: Vec<&str> = types.iter().map(|t| t.name()).collect();
+                    return Err(format!(
+                        "Field {} must be of type {}, not {}",
+                        field.name,
+                        expected.join(" or "),
+                        field.field_type.name()
+                    ));
+                }
+            }
+        }
+        for required in REQUIRED_FIELDS {
+            if !self.fields.iter().any(|field| field.name == required) {
+                return Err(format!("The schema has no {} field", required));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn field_count(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Parses a packet in the order of the schema fields
+    pub fn parse_line(&self, line: &str) -> Result<Telemetry, String> {
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .trim(csv::Trim::All)
+            .from_reader(line.as_bytes());
+        let record = csv_reader
+            .records()
+            .next()
+            .ok_or("The packet is empty")?
+            .map_err(|e| e.to_string())?;
+        if record.len() != self.fields.len() {
+            return Err(format!(
+                "Expected {} fields but got {}",
+                self.fields.len(),
+                record.len()
+            ));
+        }
+
+        let mut fields = Map::new();
+        for (field, raw) in self.fields.iter().zip(record.iter()) {
+            fields.insert(field.name.clone(), field.parse(raw)?);
+        }
+        telemetry_from_fields(fields)
+    }
+
+    /// The schema with units, resolutions and ranges in the display units
+    pub fn in_units(&self, units: &DisplayUnits) -> TelemetrySchema {
+        TelemetrySchema {
+            name: self.name.clone(),
+            fields: self.fields.iter().map(|f| f.in_units(units)).collect(),
+        }
+    }
+
+    /// Conversion of a field to the display units, `None` if it is shown as it is
+    pub fn conversion(&self, field: &str, units: &DisplayUnits) -> Option<Conversion> {
+        self.fields
+            .iter()
+            .find(|f| f.name == field)
+            .and_then(|f| f.conversion(units))
+    }
+
+    /// A copy of the packet with its values in the display units, as sent to the UI
+    pub fn to_display(&self, t: &Telemetry, units: &DisplayUnits) -> Telemetry {
+        let conversions: Vec<(&str, Conversion)> = self
+            .fields
+            .iter()
+            .filter(|f| f.field_type == FieldType::Float)
+            .filter_map(|f| Some((f.name.as_str(), f.conversion(units)?)))
+            .collect();
+        if conversions.is_empty() {
+            return t.clone();
+        }
+
+        let mut values = match serde_json::to_value(t) {
+            Ok(Value::Object(values)) => values,
+            _ => return t.clone(),
+        };
+        // Not read back from the map, they would end up among the extra fields
+        values.remove(RECEIVED_UTC_COLUMN);
+        values.remove(RECEIVED_MONOTONIC_COLUMN);
+        values.remove(RSSI_COLUMN);
+        for (name, conversion) in conversions {
+            if let Some(value) = values.get_mut(name) {
+                if let Some(number) = value
+                    .as_f64()
+                    .and_then(|v| serde_json::Number::from_f64(conversion.apply(v)))
+                {
+                    *value = Value::Number(number);
+                }
+            }
+        }
+        match telemetry_from_fields(values) {
+            Ok(converted) => Telemetry {
+                received_utc: t.received_utc,
+                received_monotonic_s: t.received_monotonic_s,
+                rssi_dbm: t.rssi_dbm,
+                ..converted
+            },
+            Err(_) => t.clone(),
+        }
+    }
+
+    /// Header row of a CSV, the receive times and signal strength are only part of the
+    /// ground station logs
+    pub fn csv_headers(&self, with_receive_times: bool) -> Vec<String> {
+        let mut headers: Vec<String> =
+            self.fields.iter().map(|f| f.csv_name.clone()).collect();
+        if with_receive_times {
+            headers.push(RECEIVED_UTC_COLUMN.to_string());
+            headers.push(RECEIVED_MONOTONIC_COLUMN.to_string());
+            headers.push(RSSI_COLUMN.to_string());
+        }
+        headers
+    }
+
+    /// A packet as a CSV row, numbers rounded to the resolution of their field
+    pub fn csv_record(&self, t: &Telemetry, with_receive_times: bool) -> Vec<String> {
+        let values = serde_json::to_value(t).unwrap_or_default();
+        let mut record: Vec<String> = self
+            .fields
+            .iter()
+            .map(|field| field.format(values.get(&field.name).unwrap_or(&Value::Null)))
+            .collect();
+        if with_receive_times {
+            record.push(
+                t.received_utc
+                    .map(|time| time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+                    .unwrap_or_default(),
+            );
+            record.push(
+                t.received_monotonic_s
+                    .map(|s| format!("{:.3}", s))
+                    .unwrap_or_default(),
+            );
+            record.push(t.rssi_dbm.map(|rssi| rssi.to_string()).unwrap_or_default());
+        }
+        record
+    }
+
+    /// Reads a CSV written by `save_csv` or the flight log back into telemetry. The
+    /// columns are matched by their header, in any order. Rows that cannot be parsed
+    /// are skipped, the number of skipped rows is returned alongside
+    pub fn read_csv(&self, path: &Path) -> Result<(Vec<Telemetry>, usize), String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Error opening file at {:?}: {}", path, e))?;
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .trim(csv::Trim::All)
+            .from_reader(file);
+        let headers = csv_reader
+            .headers()
+            .map_err(|e| format!("Error reading the header of {:?}: {}", path, e))?
+            .clone();
+        let columns: Vec<Option<&FieldDefinition>> = headers
+            .iter()
+            .map(|header| self.fields.iter().find(|f| f.matches_column(header)))
+            .collect();
+        let column_of =
+            |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+        let received_utc_column = column_of(RECEIVED_UTC_COLUMN);
+        let received_monotonic_column = column_of(RECEIVED_MONOTONIC_COLUMN);
+        let rssi_column = column_of(RSSI_COLUMN);
+
+        let parse_row = |record: &csv::StringRecord| -> Result<Telemetry, String> {
+            let mut fields = Map::new();
+            for (field, raw) in columns.iter().zip(record.iter()) {
+                if let Some(field) = field {
+                    fields.insert(field.name.clone(), field.parse(raw)?);
+                }
+            }
+            let mut telemetry = telemetry_from_fields(fields)?;
+            let received = |column: Option<usize>| {
+                column
+                    .and_then(|column| record.get(column))
+                    .filter(|raw| !raw.is_empty())
+            };
+            if let Some(time) = received(received_utc_column) {
+                telemetry.received_utc = Some(
+                    DateTime::parse_from_rfc3339(time)
+                        .map_err(|_| format!("Invalid received_utc: {:?}", time))?
+                        .with_timezone(&Utc),
+                );
+            }
+            if let Some(seconds) = received(received_monotonic_column) {
+                telemetry.received_monotonic_s =
+                    Some(seconds.parse().map_err(|_| {
+                        format!("Invalid received_monotonic_s: {:?}", seconds)
+                    })?);
+            }
+            if let Some(rssi) = received(rssi_column) {
+                telemetry.rssi_dbm = Some(
+                    rssi.parse()
+                        .map_err(|_| format!("Invalid rssi_dbm: {:?}", rssi))?,
+                );
+            }
+            Ok(telemetry)
+        };
+
+        let mut telemetry = Vec::new();
+        let mut skipped = 0;
+        for (row, result) in csv_reader.records().enumerate() {
+            match result
+                .map_err(|e| e.to_string())
+                .and_then(|r| parse_row(&r))
+            {
+                Ok(t) => telemetry.push(t),
+                Err(e) => {
+                    eprintln!("Skipping row {} of {:?}: {}", row + 1, path, e);
+                    skipped += 1;
+                }
+            }
+        }
+        Ok((telemetry, skipped))
+    }
+}
+
+/// The fields of a container packet with their units, resolutions and valid ranges,
+/// in the display units
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_telemetry_schema() -> TelemetrySchema {
+    let units = *DISPLAY_UNITS.lock().await;
+    TELEMETRY_SCHEMA.lock().await.in_units(&units)
+}
+
+/// Replaces the telemetry schema with the given file, which is copied to `~/.gcs` so
+/// it is used on the next start as well. Without a file the built-in schema is
+/// restored. Takes effect with the next connection
+#[tauri::command(rename_all = "snake_case")]
+pub async fn load_telemetry_schema(
+    schema_file: Option<String>,
+) -> Result<TelemetrySchema, String> {
+    let path = schema_path()?;
+    let schema = match schema_file {
+        Some(schema_file) => {
+            let json = std::fs::read_to_string(&schema_file)
+                .map_err(|e| format!("Error reading {}: {}", schema_file, e))?;
+            let schema = TelemetrySchema::from_json(&json)?;
+            if let Some(directory) = path.parent() {
+                std::fs::create_dir_all(directory)
+                    .map_err(|e| format!("Error creating {:?}: {}", directory, e))?;
+            }
+            std::fs::write(&path, json)
+                .map_err(|e| format!("Error writing {:?}: {}", path, e))?;
+            schema
+        }
+        None => {
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .map_err(|e| format!("Error removing {:?}: {}", path, e))?;
+            }
+            TelemetrySchema::default_schema()
+        }
+    };
+    println!("Using telemetry schema {:?}", schema.name);
+    *TELEMETRY_SCHEMA.lock().await = schema.clone();
+    Ok(schema.in_units(&*DISPLAY_UNITS.lock().await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The built-in schema with the type of one field changed
+    fn schema_with_type(name: &str, field_type: &str) -> String {
+        let mut schema: Value = serde_json::from_str(DEFAULT_SCHEMA).unwrap();
+        let field = schema["fields"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|field| field["name"] == name)
+            .unwrap();
+        field["type"] = Value::from(field_type);
+        schema.to_string()
+    }
+
+    #[test]
+    fn accepts_the_built_in_schema() {
+        let schema = TelemetrySchema::default_schema();
+        assert!(schema.field_count() > 0);
+    }
+
+    #[test]
+    fn rejects_a_known_field_of_the_wrong_type() {
+        let error =
+            TelemetrySchema::from_json(&schema_with_type("packet_count", "text"))
+                .unwrap_err();
+        assert_eq!(
+            error,
+            "Field packet_count must be of type integer, not text"
+        );
+        assert!(
+            TelemetrySchema::from_json(&schema_with_type("mode", "float")).is_err()
+        );
+        assert!(
+            TelemetrySchema::from_json(&schema_with_type("gps_sats", "float")).is_err()
+        );
+    }
+
+    #[test]
+    fn accepts_compatible_types_of_known_fields() {
+        assert!(
+            TelemetrySchema::from_json(&schema_with_type("altitude", "integer"))
+                .is_ok()
+        );
+        assert!(
+            TelemetrySchema::from_json(&schema_with_type("gps_time", "text")).is_ok()
+        );
+    }
+}
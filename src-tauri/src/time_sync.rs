@@ -0,0 +1,268 @@
+use crate::{send_message_to_device, Telemetry, TEAM_ID};
+use chrono::{DateTime, Timelike, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+/// Largest mean offset from local UTC still considered synchronised. MISSION_TIME
+/// has a resolution of one second, so anything tighter would flap
+const SYNC_TOLERANCE_S: f64 = 1.5;
+/// Minimum time covered by the samples before the drift is estimated
+const MIN_DRIFT_SPAN_S: f64 = 10.0;
+/// Number of most recent samples the estimate is based on
+const MAX_SAMPLES: usize = 300;
+
+lazy_static! {
+    static ref TIME_SYNC: Arc<tokio::sync::Mutex<TimeSyncState>> =
+        Arc::new(tokio::sync::Mutex::new(TimeSyncState::default()));
+}
+
+/// Clock the probe is asked to set its MISSION_TIME from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSyncSource {
+    /// The ground station UTC clock, sent as hh:mm:ss
+    Utc,
+    /// The probe's own GPS receiver, sent as the `GPS` keyword
+    Gps,
+}
+
+/// Comparison of the clocks for a single packet
+#[derive(Clone, Debug)]
+struct TimeSyncSample {
+    received_at: DateTime<Utc>,
+    /// MISSION_TIME minus local UTC, in seconds
+    offset_to_utc_s: f64,
+    /// MISSION_TIME minus GPS_TIME, in seconds, `None` without a GPS time
+    offset_to_gps_s: Option<f64>,
+}
+
+#[derive(Default)]
+struct TimeSyncState {
+    source: Option<TimeSyncSource>,
+    requested_at: Option<DateTime<Utc>>,
+    /// Whether a packet echoing the ST command arrived since it was sent, later
+    /// commands replace the echo
+    echo_seen: bool,
+    samples: Vec<TimeSyncSample>,
+    synchronised: bool,
+}
+
+/// Payload of the `time-sync` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeSyncStatus {
+    /// Source of the last ST command, `None` if none was sent by the backend
+    pub source: Option<TimeSyncSource>,
+    pub requested_at: Option<DateTime<Utc>>,
+    pub samples: usize,
+    /// Mean of MISSION_TIME minus local UTC, in seconds
+    pub offset_to_utc_s: Option<f64>,
+    /// Mean of MISSION_TIME minus GPS_TIME, in seconds
+    pub offset_to_gps_s: Option<f64>,
+    /// Rate at which the probe clock runs away from local UTC, in parts per million
+    pub drift_ppm: Option<f64>,
+    pub synchronised: bool,
+}
+
+/// Wraps a difference of two times of day into -12 h..12 h
+pub fn wrap_offset(offset: f64) -> f64 {
+    let offset = offset.rem_euclid(SECONDS_PER_DAY);
+    if offset > SECONDS_PER_DAY / 2.0 {
+        offset - SECONDS_PER_DAY
+    } else {
+        offset
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (count, sum) =
+        values.fold((0, 0.0), |(count, sum), value| (count + 1, sum + value));
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f64)
+    }
+}
+
+/// Least squares slope of the offset to UTC over local time
+fn drift_ppm(samples: &[TimeSyncSample]) -> Option<f64> {
+    let first = samples.first()?.received_at;
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|sample| {
+            let elapsed =
+                (sample.received_at - first).num_milliseconds() as f64 / 1000.0;
+            (elapsed, sample.offset_to_utc_s)
+        })
+        .collect();
+    let span = points.last()?.0;
+    if span < MIN_DRIFT_SPAN_S {
+        return None;
+    }
+    let mean_x = mean(points.iter().map(|point| point.0))?;
+    let mean_y = mean(points.iter().map(|point| point.1))?;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    Some(covariance / variance * 1e6)
+}
+
+impl TimeSyncState {
+    fn status(&self) -> TimeSyncStatus {
+        TimeSyncStatus {
+            source: self.source,
+            requested_at: self.requested_at,
+            samples: self.samples.len(),
+            offset_to_utc_s: mean(
+                self.samples.iter().map(|sample| sample.offset_to_utc_s),
+            ),
+            offset_to_gps_s: mean(
+                self.samples
+                    .iter()
+                    .filter_map(|sample| sample.offset_to_gps_s),
+            ),
+            drift_ppm: drift_ppm(&self.samples),
+            synchronised: self.synchronised,
+        }
+    }
+
+    /// Compares the clocks of a packet, returns the updated status or `None` when
+    /// the packet cannot be used for the estimate
+    fn observe(
+        &mut self,
+        telemetry: &Telemetry,
+        received_at: DateTime<Utc>,
+    ) -> Option<TimeSyncStatus> {
+        // Packets sent before the probe has processed the ST command still carry
+        // the old time
+        if self.requested_at.is_some() && !self.echo_seen {
+            if !telemetry.cmd_echo.starts_with("ST") {
+                return None;
+            }
+            self.echo_seen = true;
+        }
+
+        let mission_time = telemetry.mission_time_seconds()?;
+        let local_time = received_at.num_seconds_from_midnight() as f64
+            + received_at.nanosecond() as f64 / 1e9;
+        // Packets without a GPS fix report a zeroed time
+        let offset_to_gps_s = telemetry
+            .gps_time_seconds()
+            .filter(|_| telemetry.gps_sats > 0)
+            .map(|gps_time| wrap_offset(mission_time - gps_time));
+
+        self.samples.push(TimeSyncSample {
+            received_at,
+            offset_to_utc_s: wrap_offset(mission_time - local_time),
+            offset_to_gps_s,
+        });
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.remove(0);
+        }
+
+        let mut status = self.status();
+        let synchronised = status
+            .offset_to_utc_s
+            .is_some_and(|offset| offset.abs() <= SYNC_TOLERANCE_S);
+        if synchronised != self.synchronised {
+            self.synchronised = synchronised;
+            status.synchronised = synchronised;
+            println!(
+                "Mission time {}: offset to UTC {:?} s, offset to GPS {:?} s, drift {:?} ppm",
+                if synchronised {
+                    "synchronised"
+                } else {
+                    "not synchronised"
+                },
+                status.offset_to_utc_s,
+                status.offset_to_gps_s,
+                status.drift_ppm
+            );
+        }
+        Some(status)
+    }
+}
+
+/// Compares the clocks of a freshly received packet, returns the updated status
+/// or `None` when the packet cannot be used for the estimate
+pub async fn observe(telemetry: &Telemetry) -> Option<TimeSyncStatus> {
+    TIME_SYNC.lock().await.observe(telemetry, Utc::now())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn synchronise_mission_time(source: TimeSyncSource) -> Result<(), String> {
+    let time = match source {
+        TimeSyncSource::Utc => Utc::now().format("%H:%M:%S").to_string(),
+        TimeSyncSource::Gps => "GPS".to_string(),
+    };
+    let command = format!("CMD,{},ST,{}", TEAM_ID, time);
+    println!("Synchronising mission time: {}", command);
+
+    {
+        let mut state = TIME_SYNC.lock().await;
+        *state = TimeSyncState::default();
+        state.source = Some(source);
+        state.requested_at = Some(Utc::now());
+    }
+
+    send_message_to_device(command, None).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_time_sync_status() -> TimeSyncStatus {
+    TIME_SYNC.lock().await.status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn packet(mission_time: &str, cmd_echo: &str) -> Telemetry {
+        Telemetry {
+            mission_time: mission_time.to_string(),
+            cmd_echo: cmd_echo.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn samples_after_the_st_echo_even_when_later_commands_replace_it() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 10, 13, 14, 0).unwrap();
+        let mut state = TimeSyncState {
+            source: Some(TimeSyncSource::Utc),
+            requested_at: Some(start),
+            ..Default::default()
+        };
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+
+        // Still the echo of the command before ST
+        assert!(state.observe(&packet("09:00:00", "CXON"), at(1)).is_none());
+        let status = state
+            .observe(&packet("13:14:02", "ST13:14:02"), at(2))
+            .unwrap();
+        assert_eq!(status.samples, 1);
+        let status = state.observe(&packet("13:14:03", "CXON"), at(3)).unwrap();
+        assert_eq!(status.samples, 2);
+        assert!(status.synchronised);
+        assert_eq!(status.offset_to_utc_s, Some(0.0));
+    }
+
+    #[test]
+    fn samples_every_packet_without_an_st_command() {
+        let start = Utc.with_ymd_and_hms(2023, 6, 10, 13, 14, 0).unwrap();
+        let mut state = TimeSyncState::default();
+        let status = state.observe(&packet("13:14:10", "CXON"), start).unwrap();
+        assert_eq!(status.offset_to_utc_s, Some(10.0));
+        assert!(!status.synchronised);
+    }
+
+    #[test]
+    fn wraps_offsets_across_midnight() {
+        assert_eq!(wrap_offset(-86399.0), 1.0);
+        assert_eq!(wrap_offset(86399.0), -1.0);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::history;
+use crate::report::escape_xml;
+use crate::Telemetry;
+use chrono::{DateTime, NaiveTime, Utc};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+
+/// Colours assigned to states in order of appearance, as RGB
+const STATE_COLOURS: [&str; 10] = [
+    "1f77b4", "ff7f0e", "2ca02c", "d62728", "9467bd", "8c564b", "e377c2", "7f7f7f",
+    "bcbd22", "17becf",
+];
+
+/// Coordinates are stored as f32, anything past a micro degree is float noise
+fn round_coordinate(value: f32) -> f64 {
+    (value as f64 * 1e6).round() / 1e6
+}
+
+/// KML colours are written as aabbggrr
+fn kml_colour(rgb: &str) -> String {
+    format!("ff{}{}{}", &rgb[4..6], &rgb[2..4], &rgb[0..2])
+}
+
+/// Full UTC timestamp of a fix, GPS_TIME only carries the time of day so the date
+/// is taken from the receive time
+fn fix_time(t: &Telemetry) -> Option<DateTime<Utc>> {
+    let date = t.received_utc?.date_naive();
+    let time = NaiveTime::parse_from_str(t.gps_time.trim(), "%H:%M:%S").ok()?;
+    Some(date.and_time(time).and_utc())
+}
+
+/// Packets with a GPS fix, the only ones that belong on a map
+async fn track() -> Result<Vec<Telemetry>, String> {
+    let fixes: Vec<Telemetry> = history::whole_flight()
+        .await?
+        .into_iter()
+        .filter(|t| t.has_gps_fix())
+        .collect();
+    if fixes.is_empty() {
+        return Err(
+            "No GPS fix has been received, there is no track to export.".to_string()
+        );
+    }
+    Ok(fixes)
+}
+
+/// Splits the track into runs of the same STATE. Every segment after the first
+/// starts at the last point of the previous one so the drawn line has no gaps
+fn state_segments(track: &[Telemetry]) -> Vec<(&str, Vec<&Telemetry>)> {
+    let mut segments: Vec<(&str, Vec<&Telemetry>)> = Vec::new();
+    for t in track {
+        match segments.last_mut() {
+            Some((state, points)) if *state == t.state => points.push(t),
+            previous => {
+                let mut points = Vec::new();
+                if let Some((_, previous_points)) = previous {
+                    points.extend(previous_points.last().cloned());
+                }
+                points.push(t);
+                segments.push((&t.state, points));
+            }
+        }
+    }
+    segments
+}
+
+/// Colour of every state, in order of appearance
+fn state_colours(track: &[Telemetry]) -> Vec<(&str, &'static str)> {
+    let mut colours: Vec<(&str, &'static str)> = Vec::new();
+    for t in track {
+        if !colours.iter().any(|(state, _)| *state == t.state) {
+            colours
+                .push((&t.state, STATE_COLOURS[colours.len() % STATE_COLOURS.len()]));
+        }
+    }
+    colours
+}
+
+fn write_file(output_file: &str, content: String) -> Result<(), String> {
+    std::fs::write(output_file, content)
+        .map_err(|e| format!("Error writing {}: {}", output_file, e))?;
+    println!("Track written to {}", output_file);
+    Ok(())
+}
+
+fn kml(track: &[Telemetry]) -> String {
+    let colours = state_colours(track);
+    let style_id = |state: &str| {
+        let index = colours.iter().position(|(s, _)| *s == state).unwrap_or(0);
+        format!("state{}", index)
+    };
+
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n\
+         <name>CanSat flight</name>\n",
+    );
+    for (state, colour) in &colours {
+        let _ = writeln!(
+            kml,
+            "<Style id=\"{}\"><LineStyle><color>{}</color><width>3</width></LineStyle>\
+             <PolyStyle><color>7f{}</color></PolyStyle></Style>",
+            style_id(state),
+            kml_colour(colour),
+            // Half transparent curtain below the track
+            &kml_colour(colour)[2..]
+        );
+    }
+
+    for (state, points) in state_segments(track) {
+        let coordinates: Vec<String> = points
+            .iter()
+            .map(|t| {
+                format!(
+                    "{},{},{:.1}",
+                    round_coordinate(t.gps_longitude),
+                    round_coordinate(t.gps_latitude),
+                    t.gps_altitude
+                )
+            })
+            .collect();
+        let _ = writeln!(
+            kml,
+            "<Placemark><name>{state}</name><description>{start} - {end}</description>\
+             <styleUrl>#{style}</styleUrl><LineString><extrude>1</extrude>\
+             <tessellate>1</tessellate><altitudeMode>absolute</altitudeMode>\
+             <coordinates>{coordinates}</coordinates></LineString></Placemark>",
+            state = escape_xml(state),
+            start = escape_xml(&points[0].mission_time),
+            end = escape_xml(&points[points.len() - 1].mission_time),
+            style = style_id(state),
+            coordinates = coordinates.join(" "),
+        );
+    }
+
+    let first = &track[0];
+    let last = &track[track.len() - 1];
+    for (name, t) in [("First fix", first), ("Last fix", last)] {
+        let _ = writeln!(
+            kml,
+            "<Placemark><name>{}</name><description>{} {}</description>\
+             <Point><altitudeMode>absolute</altitudeMode>\
+             <coordinates>{},{},{:.1}</coordinates></Point></Placemark>",
+            name,
+            escape_xml(&t.mission_time),
+            escape_xml(&t.state),
+            round_coordinate(t.gps_longitude),
+            round_coordinate(t.gps_latitude),
+            t.gps_altitude
+        );
+    }
+
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+fn gpx(track: &[Telemetry]) -> String {
+    let mut gpx = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <gpx version=\"1.1\" creator=\"ground-control-station\" \
+         xmlns=\"http://www.topografix.com/GPX/1/1\">\n\
+         <trk>\n<name>CanSat flight</name>\n<trkseg>\n",
+    );
+    for t in track {
+        let _ = write!(
+            gpx,
+            "<trkpt lat=\"{}\" lon=\"{}\"><ele>{:.1}</ele>",
+            round_coordinate(t.gps_latitude),
+            round_coordinate(t.gps_longitude),
+            t.gps_altitude
+        );
+        if let Some(time) = fix_time(t) {
+            let _ = write!(gpx, "<time>{}</time>", time.format("%Y-%m-%dT%H:%M:%SZ"));
+        }
+        let _ = writeln!(
+            gpx,
+            // GPX 1.1 requires desc before sat
+            "<desc>{}</desc><sat>{}</sat></trkpt>",
+            escape_xml(&t.state),
+            t.gps_sats
+        );
+    }
+    gpx.push_str("</trkseg>\n</trk>\n</gpx>\n");
+    gpx
+}
+
+fn geojson(track: &[Telemetry]) -> Value {
+    let colours = state_colours(track);
+    let position = |t: &Telemetry| {
+        json!([
+            round_coordinate(t.gps_longitude),
+            round_coordinate(t.gps_latitude),
+            t.gps_altitude
+        ])
+    };
+
+    let mut features: Vec<Value> = state_segments(track)
+        .into_iter()
+        .map(|(state, points)| {
+            let colour = colours
+                .iter()
+                .find(|(s, _)| *s == state)
+                .map(|(_, colour)| format!("#{}", colour));
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": points.iter().map(|t| position(t)).collect::<Vec<_>>(),
+                },
+                "properties": {
+                    "state": state,
+                    "start_mission_time": points[0].mission_time,
+                    "end_mission_time": points[points.len() - 1].mission_time,
+                    "stroke": colour,
+                },
+            })
+        })
+        .collect();
+
+    features.extend(track.iter().map(|t| {
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": position(t) },
+            "properties": {
+                "mission_time": t.mission_time,
+                "gps_time": t.gps_time,
+                "time": fix_time(t),
+                "packet_count": t.packet_count,
+                "state": t.state,
+                "altitude": t.altitude,
+                "gps_altitude": t.gps_altitude,
+                "gps_sats": t.gps_sats,
+            },
+        })
+    }));
+
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// Writes the GPS track as KML, extruded to the ground and coloured by state
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_kml(output_file: String) -> Result<(), String> {
+    let track = track().await?;
+    write_file(&output_file, kml(&track))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_gpx(output_file: String) -> Result<(), String> {
+    let track = track().await?;
+    write_file(&output_file, gpx(&track))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_geojson(output_file: String) -> Result<(), String> {
+    let track = track().await?;
+    let geojson = serde_json::to_string_pretty(&geojson(&track))
+        .map_err(|e| format!("Error serializing GeoJSON: {}", e))?;
+    write_file(&output_file, geojson)
+}
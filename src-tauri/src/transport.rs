@@ -0,0 +1,224 @@
+use crate::line_codec::{Frame, TelemetryLineCodec};
+use crate::xbee::{ApiFrame, FrameError, XbeeApiCodec};
+use bytes::BytesMut;
+use serde::{Deserialize, Serialize};
+use tokio_util::codec::Decoder;
+
+/// How the serial stream is framed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    /// Lines as the probe sends them, an XBee in transparent mode (AP=0)
+    #[default]
+    Transparent,
+    /// XBee API frames (AP=1)
+    Api,
+    /// XBee API frames with escaped control bytes (AP=2)
+    ApiEscaped,
+}
+
+impl Framing {
+    /// Whether control bytes are escaped, `None` in transparent mode
+    pub fn api_escaping(&self) -> Option<bool> {
+        match self {
+            Framing::Transparent => None,
+            Framing::Api => Some(false),
+            Framing::ApiEscaped => Some(true),
+        }
+    }
+}
+
+/// Something read from the serial stream
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerialFrame {
+    /// A line of the probe, with the signal strength of the radio frame that
+    /// completed it in API mode
+    Line { frame: Frame, rssi_dbm: Option<i16> },
+    /// An API frame other than received data
+    Api(ApiFrame),
+}
+
+/// Splits the serial stream into lines, in API mode the lines are taken from the
+/// data of the RX frames, which may split a line across several frames
+pub struct SerialCodec {
+    lines: TelemetryLineCodec,
+    api: Option<XbeeApiCodec>,
+    /// Data of the RX frames that does not form a complete line yet
+    received: BytesMut,
+    rssi_dbm: Option<i16>,
+    activity: bool,
+}
+
+impl SerialCodec {
+    pub fn new(framing: Framing, max_line_length: usize) -> Self {
+        SerialCodec {
+            lines: TelemetryLineCodec::new(max_line_length),
+            api: framing.api_escaping().map(XbeeApiCodec::new),
+            received: BytesMut::new(),
+            rssi_dbm: None,
+            activity: false,
+        }
+    }
+
+    /// Whether any bytes arrived since the last call, see
+    /// `TelemetryLineCodec::take_activity`
+    pub fn take_activity(&mut self) -> bool {
+        let lines = self.lines.take_activity();
+        std::mem::take(&mut self.activity) || lines
+    }
+
+    fn line(&self, frame: Option<Frame>) -> Option<SerialFrame> {
+        frame.map(|frame| SerialFrame::Line {
+            frame,
+            rssi_dbm: self.rssi_dbm,
+        })
+    }
+}
+
+impl Decoder for SerialCodec {
+    type Item = SerialFrame;
+    type Error = std::io::Error;
+
+    fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<SerialFrame>, std::io::Error> {
+        let api = match self.api.as_mut() {
+            Some(api) => api,
+            None => {
+                let frame = self.lines.decode(buf)?;
+                return Ok(self.line(frame));
+            }
+        };
+        if !buf.is_empty() {
+            self.activity = true;
+        }
+        loop {
+            // Lines completed by earlier frames come first
+            if let Some(frame) = self.lines.decode(&mut self.received)? {
+                return Ok(Some(SerialFrame::Line {
+                    frame,
+                    rssi_dbm: self.rssi_dbm,
+                }));
+            }
+            match api.decode(buf)? {
+                None => return Ok(None),
+                Some(ApiFrame::Receive { rssi_dbm, data, .. }) => {
+                    self.rssi_dbm = rssi_dbm;
+                    self.received.extend_from_slice(&data);
+                }
+                Some(frame) => return Ok(Some(SerialFrame::Api(frame))),
+            }
+        }
+    }
+
+    fn decode_eof(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<SerialFrame>, std::io::Error> {
+        if self.api.is_none() {
+            let frame = self.lines.decode_eof(buf)?;
+            return Ok(self.line(frame));
+        }
+        if let Some(frame) = self.decode(buf)? {
+            return Ok(Some(frame));
+        }
+        if !buf.is_empty() {
+            let discarded = buf.len();
+            buf.clear();
+            return Ok(Some(SerialFrame::Api(ApiFrame::Corrupted {
+                discarded,
+                error: FrameError::Truncated,
+            })));
+        }
+        let frame = self.lines.decode_eof(&mut self.received)?;
+        Ok(self.line(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xbee::encode_frame;
+
+    /// Frame data of an 802.15.4 RX frame with the given RSSI
+    fn rx_frame(rssi: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x81, 0x12, 0x34, rssi, 0x00];
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    fn decode_all(codec: &mut SerialCodec, buf: &mut BytesMut) -> Vec<SerialFrame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = codec.decode(buf).unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn reads_lines_in_transparent_mode() {
+        let mut codec = SerialCodec::new(Framing::Transparent, 256);
+        let mut buf = BytesMut::from("1082,00:01\r\n1082,00:02\n");
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            [
+                SerialFrame::Line {
+                    frame: Frame::Line("1082,00:01".to_string()),
+                    rssi_dbm: None,
+                },
+                SerialFrame::Line {
+                    frame: Frame::Line("1082,00:02".to_string()),
+                    rssi_dbm: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_a_line_split_across_two_rx_frames() {
+        let mut codec = SerialCodec::new(Framing::ApiEscaped, 256);
+        let mut buf =
+            BytesMut::from(&encode_frame(&rx_frame(0x30, b"1082,00:"), true)[..]);
+        assert_eq!(decode_all(&mut codec, &mut buf), []);
+        buf.extend_from_slice(&encode_frame(&rx_frame(0x28, b"01,1\n1082"), true));
+        // The line carries the signal strength of the frame that completed it
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            [SerialFrame::Line {
+                frame: Frame::Line("1082,00:01,1".to_string()),
+                rssi_dbm: Some(-40),
+            }]
+        );
+        assert_eq!(
+            codec.decode_eof(&mut buf).unwrap(),
+            Some(SerialFrame::Line {
+                frame: Frame::Line("1082".to_string()),
+                rssi_dbm: Some(-40),
+            })
+        );
+    }
+
+    #[test]
+    fn passes_other_api_frames_through() {
+        let mut codec = SerialCodec::new(Framing::Api, 256);
+        let mut buf = BytesMut::from(&encode_frame(&[0x89, 0x05, 0x01], false)[..]);
+        let truncated = encode_frame(&rx_frame(0x28, b"1082"), false);
+        buf.extend_from_slice(&truncated[..4]);
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            [SerialFrame::Api(ApiFrame::TransmitStatus {
+                frame_id: 5,
+                status: 1,
+                retries: None,
+            })]
+        );
+        assert_eq!(
+            codec.decode_eof(&mut buf).unwrap(),
+            Some(SerialFrame::Api(ApiFrame::Corrupted {
+                discarded: 4,
+                error: FrameError::Truncated,
+            }))
+        );
+    }
+}
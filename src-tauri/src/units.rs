@@ -0,0 +1,100 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+lazy_static! {
+    pub static ref DISPLAY_UNITS: Arc<tokio::sync::Mutex<DisplayUnits>> =
+        Arc::new(tokio::sync::Mutex::new(DisplayUnits::default()));
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthUnit {
+    #[default]
+    Metres,
+    Feet,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PressureUnit {
+    #[default]
+    Kilopascals,
+    Hectopascals,
+}
+
+/// Units the UI shows telemetry in. Logs, exports and the database always keep the
+/// units of the telemetry schema
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayUnits {
+    pub length: LengthUnit,
+    pub temperature: TemperatureUnit,
+    pub pressure: PressureUnit,
+}
+
+/// A linear conversion `value * scale + offset` from a unit of the schema
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Conversion {
+    pub unit: &'static str,
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Conversion {
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+
+    /// For differences such as a resolution, where the offset does not apply
+    pub fn apply_step(&self, step: f64) -> f64 {
+        step * self.scale
+    }
+}
+
+impl DisplayUnits {
+    /// The conversion of values in the given schema unit, `None` if they are shown as
+    /// they are
+    pub fn conversion(&self, unit: &str) -> Option<Conversion> {
+        match unit {
+            "m" if self.length == LengthUnit::Feet => Some(Conversion {
+                unit: "ft",
+                scale: 1.0 / 0.3048,
+                offset: 0.0,
+            }),
+            "°C" if self.temperature == TemperatureUnit::Fahrenheit => {
+                Some(Conversion {
+                    unit: "°F",
+                    scale: 1.8,
+                    offset: 32.0,
+                })
+            }
+            "kPa" if self.pressure == PressureUnit::Hectopascals => Some(Conversion {
+                unit: "hPa",
+                scale: 10.0,
+                offset: 0.0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Changes the units of the telemetry sent to the UI, applies to events sent from now
+/// on. The UI reloads `get_telemetry_schema` for the new labels
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_display_units(units: DisplayUnits) {
+    println!("New display units: {:?}", units);
+    *DISPLAY_UNITS.lock().await = units;
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_display_units() -> DisplayUnits {
+    *DISPLAY_UNITS.lock().await
+}
@@ -0,0 +1,672 @@
+use bytes::{Buf, BytesMut};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use tokio_util::codec::Decoder;
+
+const START_DELIMITER: u8 = 0x7E;
+const ESCAPE: u8 = 0x7D;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+/// Escaped bytes are sent as `ESCAPE` followed by the byte XOR this value
+const ESCAPE_XOR: u8 = 0x20;
+/// Longest frame data accepted, longer lengths mean the delimiter was a data byte
+const MAX_FRAME_LENGTH: usize = 300;
+
+/// Frame type of a Transmit Request of Zigbee and DigiMesh radios
+const TRANSMIT_REQUEST: u8 = 0x10;
+/// Frame type of a TX Request with 64-bit address of 802.15.4 radios
+const TX_REQUEST_64: u8 = 0x00;
+/// Frame type of a command to the local radio
+const AT_COMMAND: u8 = 0x08;
+/// 16-bit address to use when only the 64-bit address is known
+const UNKNOWN_ADDRESS_16: u16 = 0xFFFE;
+
+lazy_static! {
+    pub static ref XBEE_CONFIG: Arc<tokio::sync::Mutex<XbeeConfig>> =
+        Arc::new(tokio::sync::Mutex::new(XbeeConfig::default()));
+    pub static ref XBEE_STATS: Arc<tokio::sync::Mutex<XbeeStats>> =
+        Arc::new(tokio::sync::Mutex::new(XbeeStats::default()));
+    /// Commands sent as Transmit Requests by frame ID, until their status arrives
+    static ref PENDING_TRANSMISSIONS: Arc<tokio::sync::Mutex<HashMap<u8, String>>> =
+        Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+}
+
+/// Frame IDs run from 1 to 255, 0 would tell the radio not to report a status
+static NEXT_FRAME_ID: AtomicU8 = AtomicU8::new(1);
+/// Whether the local radio delivered an 802.15.4 RX frame (0x80 or 0x81) since the
+/// connection was opened
+static IEEE_802_15_4_SEEN: AtomicBool = AtomicBool::new(false);
+
+/// Firmware of the local radio, it decides the frame type of transmit requests
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RadioProtocol {
+    /// 802.15.4 once an 802.15.4 RX frame was received, Zigbee otherwise
+    #[default]
+    Auto,
+    /// Zigbee and DigiMesh radios, Transmit Request 0x10
+    Zigbee,
+    /// 802.15.4 radios (XBee Series 1), TX Request 0x00
+    Ieee802154,
+}
+
+impl RadioProtocol {
+    fn is_802_15_4(&self) -> bool {
+        match self {
+            RadioProtocol::Auto => IEEE_802_15_4_SEEN.load(Ordering::Relaxed),
+            RadioProtocol::Zigbee => false,
+            RadioProtocol::Ieee802154 => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct XbeeConfig {
+    /// 64-bit address of the radio in the probe as 16 hex digits, commands are
+    /// broadcast with 000000000000FFFF
+    pub destination_address: String,
+    #[serde(default)]
+    pub protocol: RadioProtocol,
+}
+
+impl Default for XbeeConfig {
+    fn default() -> Self {
+        XbeeConfig {
+            destination_address: "000000000000FFFF".to_string(),
+            protocol: RadioProtocol::Auto,
+        }
+    }
+}
+
+fn parse_address(address: &str) -> Result<u64, String> {
+    if address.len() != 16 {
+        return Err(format!(
+            "The address {:?} must have 16 hex digits.",
+            address
+        ));
+    }
+    u64::from_str_radix(address, 16)
+        .map_err(|_| format!("Invalid address {:?}.", address))
+}
+
+/// Counters of the API frames of the current connection
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct XbeeStats {
+    pub checksum_errors: u64,
+    /// Bytes outside of a frame, e.g. left over from transparent mode
+    pub discarded_bytes: u64,
+    /// Commands the probe radio acknowledged
+    pub delivered: u64,
+    /// Commands the radio gave up on
+    pub failed: u64,
+    /// Signal strength of the last received frame that reported one
+    pub last_rssi_dbm: Option<i16>,
+}
+
+/// Delivery report of a command, payload of the `transmit-status` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransmitStatus {
+    pub frame_id: u8,
+    pub delivered: bool,
+    /// Delivery status code of the radio, 0 on success
+    pub status: u8,
+    pub description: String,
+    /// Retransmissions needed, only reported by Zigbee and DigiMesh radios
+    pub retries: Option<u8>,
+    /// The command the status belongs to, if it was sent in this session
+    pub command: Option<String>,
+}
+
+fn delivery_status_description(status: u8) -> &'static str {
+    match status {
+        0x00 => "Success",
+        0x01 => "No ACK received",
+        0x02 => "CCA failure",
+        0x03 => "Purged, the transmission was not attempted",
+        0x21 => "Network ACK failure",
+        0x22 => "Not joined to network",
+        0x24 => "Address not found",
+        0x25 => "Route not found",
+        0x74 => "Payload too large",
+        _ => "Unknown delivery status",
+    }
+}
+
+/// Why bytes were dropped instead of forming a frame
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// Bytes before the start delimiter, e.g. left over from transparent mode
+    OutsideFrame,
+    /// The length was zero or too long, the delimiter was a data byte
+    InvalidLength(usize),
+    /// A delimiter inside an escaped frame started the next frame
+    CutShort,
+    ChecksumMismatch,
+    /// The frame is shorter than its type requires
+    TooShort(u8),
+    /// The stream ended in the middle of a frame
+    Truncated,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::OutsideFrame => write!(f, "bytes outside of a frame"),
+            FrameError::InvalidLength(length) => {
+                write!(f, "frame length of {} bytes", length)
+            }
+            FrameError::CutShort => write!(f, "the frame was cut short"),
+            FrameError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            FrameError::TooShort(frame_type) => {
+                write!(f, "frame 0x{:02X} is too short", frame_type)
+            }
+            FrameError::Truncated => write!(f, "the stream ended inside a frame"),
+        }
+    }
+}
+
+/// An API frame received from the local radio
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApiFrame {
+    /// Data received over the air, with the signal strength if the firmware reports
+    /// it in the frame
+    Receive {
+        source: String,
+        rssi_dbm: Option<i16>,
+        data: Vec<u8>,
+    },
+    TransmitStatus {
+        frame_id: u8,
+        status: u8,
+        retries: Option<u8>,
+    },
+    /// Answer of the local radio to an AT command, `status` is 0 on success
+    AtResponse {
+        frame_id: u8,
+        command: String,
+        status: u8,
+        data: Vec<u8>,
+    },
+    /// Frames the ground station does not use
+    Other { frame_type: u8, data: Vec<u8> },
+    /// Bytes that did not form a valid frame and were dropped
+    Corrupted { discarded: usize, error: FrameError },
+}
+
+fn hex_address(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+impl ApiFrame {
+    /// Interprets the frame data, the bytes between the length and the checksum
+    fn parse(frame: &[u8]) -> ApiFrame {
+        // The length is checked to be at least one when decoding
+        let (frame_type, data) = (frame[0], &frame[1..]);
+        let too_short = |frame_type: u8| ApiFrame::Corrupted {
+            discarded: frame.len(),
+            error: FrameError::TooShort(frame_type),
+        };
+        match frame_type {
+            // Receive Packet of Zigbee and DigiMesh: address, 16-bit address, options
+            0x90 if data.len() >= 11 => ApiFrame::Receive {
+                source: hex_address(&data[..8]),
+                rssi_dbm: None,
+                data: data[11..].to_vec(),
+            },
+            // 802.15.4 RX with 64-bit address: address, RSSI, options
+            0x80 if data.len() >= 10 => ApiFrame::Receive {
+                source: hex_address(&data[..8]),
+                rssi_dbm: Some(-(data[8] as i16)),
+                data: data[10..].to_vec(),
+            },
+            // 802.15.4 RX with 16-bit address: address, RSSI, options
+            0x81 if data.len() >= 4 => ApiFrame::Receive {
+                source: hex_address(&data[..2]),
+                rssi_dbm: Some(-(data[2] as i16)),
+                data: data[4..].to_vec(),
+            },
+            // Extended Transmit Status: frame ID, 16-bit address, retries, delivery
+            // status, discovery status
+            0x8B if data.len() >= 6 => ApiFrame::TransmitStatus {
+                frame_id: data[0],
+                status: data[4],
+                retries: Some(data[3]),
+            },
+            // 802.15.4 TX Status: frame ID, status
+            0x89 if data.len() >= 2 => ApiFrame::TransmitStatus {
+                frame_id: data[0],
+                status: data[1],
+                retries: None,
+            },
+            // AT Command Response: frame ID, command, status, value
+            0x88 if data.len() >= 4 => ApiFrame::AtResponse {
+                frame_id: data[0],
+                command: String::from_utf8_lossy(&data[1..3]).to_string(),
+                status: data[3],
+                data: data[4..].to_vec(),
+            },
+            0x90 | 0x80 | 0x81 | 0x8B | 0x89 | 0x88 => too_short(frame_type),
+            _ => ApiFrame::Other {
+                frame_type,
+                data: data.to_vec(),
+            },
+        }
+    }
+}
+
+/// Reads the API frames of an XBee in API mode 1 (`escaped` false) or 2
+pub struct XbeeApiCodec {
+    escaped: bool,
+}
+
+impl XbeeApiCodec {
+    pub fn new(escaped: bool) -> Self {
+        XbeeApiCodec { escaped }
+    }
+
+    /// Unescapes the frame after the delimiter until `needed` bytes are there. Returns
+    /// the bytes and how many raw bytes they took, or `None` if they are not all there
+    fn read(&self, raw: &[u8], needed: usize) -> Option<(Vec<u8>, usize)> {
+        let mut bytes = Vec::with_capacity(needed);
+        let mut index = 0;
+        while bytes.len() < needed {
+            let byte = *raw.get(index)?;
+            if self.escaped && byte == ESCAPE {
+                bytes.push(raw.get(index + 1)? ^ ESCAPE_XOR);
+                index += 2;
+            } else {
+                bytes.push(byte);
+                index += 1;
+            }
+        }
+        Some((bytes, index))
+    }
+}
+
+impl Decoder for XbeeApiCodec {
+    type Item = ApiFrame;
+    type Error = std::io::Error;
+
+    fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<ApiFrame>, std::io::Error> {
+        match buf.iter().position(|byte| *byte == START_DELIMITER) {
+            Some(0) => {}
+            Some(start) => {
+                buf.advance(start);
+                return Ok(Some(ApiFrame::Corrupted {
+                    discarded: start,
+                    error: FrameError::OutsideFrame,
+                }));
+            }
+            None if buf.is_empty() => return Ok(None),
+            None => {
+                let discarded = buf.len();
+                buf.clear();
+                return Ok(Some(ApiFrame::Corrupted {
+                    discarded,
+                    error: FrameError::OutsideFrame,
+                }));
+            }
+        }
+
+        let (length, length_bytes) = match self.read(&buf[1..], 2) {
+            Some((length, used)) => (u16::from_be_bytes([length[0], length[1]]), used),
+            None => return Ok(None),
+        };
+        let length = length as usize;
+        if length == 0 || length > MAX_FRAME_LENGTH {
+            // Not a frame after all, look for the next delimiter
+            buf.advance(1);
+            return Ok(Some(ApiFrame::Corrupted {
+                discarded: 1,
+                error: FrameError::InvalidLength(length),
+            }));
+        }
+        let (frame, frame_bytes) = match self.read(&buf[1 + length_bytes..], length + 1)
+        {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let used = 1 + length_bytes + frame_bytes;
+        if self.escaped && buf[1..used].contains(&START_DELIMITER) {
+            // A delimiter inside an escaped frame starts the next frame, this one was
+            // cut short
+            let cut = 1 + buf[1..used]
+                .iter()
+                .position(|byte| *byte == START_DELIMITER)
+                .unwrap_or_default();
+            buf.advance(cut);
+            return Ok(Some(ApiFrame::Corrupted {
+                discarded: cut,
+                error: FrameError::CutShort,
+            }));
+        }
+        buf.advance(used);
+
+        let sum = frame.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if sum != 0xFF {
+            return Ok(Some(ApiFrame::Corrupted {
+                discarded: used,
+                error: FrameError::ChecksumMismatch,
+            }));
+        }
+        if matches!(frame[0], 0x80 | 0x81) {
+            // Only 802.15.4 firmware sends these, see `RadioProtocol::Auto`
+            IEEE_802_15_4_SEEN.store(true, Ordering::Relaxed);
+        }
+        Ok(Some(ApiFrame::parse(&frame[..length])))
+    }
+}
+
+/// Wraps frame data in an API frame with delimiter, length and checksum
+pub fn encode_frame(frame: &[u8], escaped: bool) -> Vec<u8> {
+    let checksum = 0xFF - frame.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    let mut body = (frame.len() as u16).to_be_bytes().to_vec();
+    body.extend_from_slice(frame);
+    body.push(checksum);
+
+    let mut encoded = vec![START_DELIMITER];
+    for byte in body {
+        if escaped && matches!(byte, START_DELIMITER | ESCAPE | XON | XOFF) {
+            encoded.push(ESCAPE);
+            encoded.push(byte ^ ESCAPE_XOR);
+        } else {
+            encoded.push(byte);
+        }
+    }
+    encoded
+}
+
+fn next_frame_id() -> u8 {
+    match NEXT_FRAME_ID.fetch_add(1, Ordering::Relaxed) {
+        0 => NEXT_FRAME_ID.fetch_add(1, Ordering::Relaxed),
+        frame_id => frame_id,
+    }
+}
+
+/// An AT command for the local radio, e.g. DB for the signal strength of the last
+/// received packet. Returns the frame and its ID to match the response with
+pub fn at_command(command: &str, parameter: &[u8], escaped: bool) -> (Vec<u8>, u8) {
+    let frame_id = next_frame_id();
+    let mut frame = vec![AT_COMMAND, frame_id];
+    frame.extend_from_slice(command.as_bytes());
+    frame.extend_from_slice(parameter);
+    (encode_frame(&frame, escaped), frame_id)
+}
+
+/// The frame data of a transmit request, 802.15.4 radios only take TX Request 0x00
+/// and silently drop Transmit Request 0x10
+fn transmit_frame(
+    frame_id: u8,
+    destination: u64,
+    payload: &[u8],
+    ieee_802_15_4: bool,
+) -> Vec<u8> {
+    let mut frame = if ieee_802_15_4 {
+        let mut frame = vec![TX_REQUEST_64, frame_id];
+        frame.extend_from_slice(&destination.to_be_bytes());
+        // Options, left to the radio
+        frame.push(0);
+        frame
+    } else {
+        let mut frame = vec![TRANSMIT_REQUEST, frame_id];
+        frame.extend_from_slice(&destination.to_be_bytes());
+        frame.extend_from_slice(&UNKNOWN_ADDRESS_16.to_be_bytes());
+        // Broadcast radius and options, both left to the radio
+        frame.extend_from_slice(&[0, 0]);
+        frame
+    };
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// A command as a transmit request to the configured destination. The frame ID is
+/// remembered so the `transmit-status` event can name the command
+pub async fn transmit_request(
+    payload: &[u8],
+    command: &str,
+    escaped: bool,
+) -> Result<Vec<u8>, String> {
+    let config = XBEE_CONFIG.lock().await.clone();
+    let destination = parse_address(&config.destination_address)?;
+    let frame_id = next_frame_id();
+    let frame = transmit_frame(
+        frame_id,
+        destination,
+        payload,
+        config.protocol.is_802_15_4(),
+    );
+
+    PENDING_TRANSMISSIONS
+        .lock()
+        .await
+        .insert(frame_id, command.to_string());
+    Ok(encode_frame(&frame, escaped))
+}
+
+/// Matches a status to its command and counts it
+pub async fn transmit_status(
+    frame_id: u8,
+    status: u8,
+    retries: Option<u8>,
+) -> TransmitStatus {
+    let command = PENDING_TRANSMISSIONS.lock().await.remove(&frame_id);
+    let delivered = status == 0;
+    let mut stats = XBEE_STATS.lock().await;
+    if delivered {
+        stats.delivered += 1;
+    } else {
+        stats.failed += 1;
+    }
+    TransmitStatus {
+        frame_id,
+        delivered,
+        status,
+        description: delivery_status_description(status).to_string(),
+        retries,
+        command,
+    }
+}
+
+pub async fn reset_stats() {
+    IEEE_802_15_4_SEEN.store(false, Ordering::Relaxed);
+    *XBEE_STATS.lock().await = XbeeStats::default();
+    PENDING_TRANSMISSIONS.lock().await.clear();
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_xbee_config(config: XbeeConfig) -> Result<(), String> {
+    parse_address(&config.destination_address)?;
+    println!("New XBee config: {:?}", config);
+    *XBEE_CONFIG.lock().await = config;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_xbee_config() -> XbeeConfig {
+    XBEE_CONFIG.lock().await.clone()
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_xbee_stats() -> XbeeStats {
+    XBEE_STATS.lock().await.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame data of a Zigbee Receive Packet from 0013A20040A1B2C3
+    fn receive_packet(data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x90, 0x00, 0x13, 0xA2, 0x00, 0x40, 0xA1, 0xB2, 0xC3];
+        frame.extend_from_slice(&[0xFF, 0xFE, 0x01]);
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    fn decode_all(codec: &mut XbeeApiCodec, bytes: &[u8]) -> Vec<ApiFrame> {
+        let mut buf = BytesMut::from(bytes);
+        let mut frames = Vec::new();
+        while let Some(frame) = codec.decode(&mut buf).unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn encodes_the_reference_frame() {
+        // AT command AP with frame ID 1, as in the XBee manual
+        assert_eq!(
+            encode_frame(&[0x08, 0x01, b'A', b'P'], false),
+            [0x7E, 0x00, 0x04, 0x08, 0x01, 0x41, 0x50, 0x65]
+        );
+    }
+
+    #[test]
+    fn round_trips_frames_with_and_without_escaping() {
+        // Every byte that needs escaping, in the data and in the checksum
+        let data = [b'1', 0x7E, 0x7D, 0x11, 0x13, b'\n'];
+        for escaped in [false, true] {
+            let encoded = encode_frame(&receive_packet(&data), escaped);
+            if escaped {
+                assert!(!encoded[1..].contains(&START_DELIMITER));
+            }
+            assert_eq!(
+                decode_all(&mut XbeeApiCodec::new(escaped), &encoded),
+                [ApiFrame::Receive {
+                    source: "0013A20040A1B2C3".to_string(),
+                    rssi_dbm: None,
+                    data: data.to_vec(),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn waits_for_the_rest_of_a_frame() {
+        let encoded = encode_frame(&receive_packet(b"1082"), true);
+        let mut codec = XbeeApiCodec::new(true);
+        let mut buf = BytesMut::from(&encoded[..encoded.len() - 3]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&encoded[encoded.len() - 3..]);
+        assert!(matches!(
+            codec.decode(&mut buf).unwrap(),
+            Some(ApiFrame::Receive { .. })
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn reports_a_checksum_mismatch() {
+        let mut encoded = encode_frame(&receive_packet(b"1082"), false);
+        let last = encoded.len() - 1;
+        encoded[last] ^= 0x01;
+        assert_eq!(
+            decode_all(&mut XbeeApiCodec::new(false), &encoded),
+            [ApiFrame::Corrupted {
+                discarded: encoded.len(),
+                error: FrameError::ChecksumMismatch,
+            }]
+        );
+    }
+
+    #[test]
+    fn resyncs_on_a_delimiter_inside_an_escaped_frame() {
+        let first = encode_frame(&receive_packet(b"1082,00:01"), true);
+        let second = encode_frame(&receive_packet(b"1082,00:02"), true);
+        // The first frame loses its last bytes and the next one starts inside it
+        let mut bytes = first[..8].to_vec();
+        bytes.extend_from_slice(&second);
+        let frames = decode_all(&mut XbeeApiCodec::new(true), &bytes);
+        assert_eq!(
+            frames[0],
+            ApiFrame::Corrupted {
+                discarded: 8,
+                error: FrameError::CutShort,
+            }
+        );
+        assert_eq!(
+            frames[1..],
+            [ApiFrame::Receive {
+                source: "0013A20040A1B2C3".to_string(),
+                rssi_dbm: None,
+                data: b"1082,00:02".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_bytes_outside_of_frames() {
+        let mut bytes = b"OK\r".to_vec();
+        bytes.extend_from_slice(&encode_frame(&[0x89, 0x05, 0x00], false));
+        assert_eq!(
+            decode_all(&mut XbeeApiCodec::new(false), &bytes),
+            [
+                ApiFrame::Corrupted {
+                    discarded: 3,
+                    error: FrameError::OutsideFrame,
+                },
+                ApiFrame::TransmitStatus {
+                    frame_id: 5,
+                    status: 0,
+                    retries: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_802_15_4_receive_frames() {
+        let mut frame = vec![0x80, 0x00, 0x13, 0xA2, 0x00, 0x40, 0xA1, 0xB2, 0xC3];
+        // RSSI of -40 dBm and options
+        frame.extend_from_slice(&[0x28, 0x00]);
+        frame.extend_from_slice(b"1082");
+        assert_eq!(
+            ApiFrame::parse(&frame),
+            ApiFrame::Receive {
+                source: "0013A20040A1B2C3".to_string(),
+                rssi_dbm: Some(-40),
+                data: b"1082".to_vec(),
+            }
+        );
+        assert_eq!(
+            ApiFrame::parse(&[0x81, 0x12, 0x34, 0x30, 0x00, b'1']),
+            ApiFrame::Receive {
+                source: "1234".to_string(),
+                rssi_dbm: Some(-48),
+                data: b"1".to_vec(),
+            }
+        );
+        assert_eq!(
+            ApiFrame::parse(&[0x80, 0x00]),
+            ApiFrame::Corrupted {
+                discarded: 2,
+                error: FrameError::TooShort(0x80),
+            }
+        );
+    }
+
+    #[test]
+    fn builds_transmit_requests_for_both_protocols() {
+        let destination = 0x0013A20040A1B2C3;
+        assert_eq!(
+            transmit_frame(7, destination, b"CX", false),
+            [
+                0x10, 0x07, 0x00, 0x13, 0xA2, 0x00, 0x40, 0xA1, 0xB2, 0xC3, 0xFF, 0xFE,
+                0x00, 0x00, b'C', b'X'
+            ]
+        );
+        assert_eq!(
+            transmit_frame(7, destination, b"CX", true),
+            [
+                0x00, 0x07, 0x00, 0x13, 0xA2, 0x00, 0x40, 0xA1, 0xB2, 0xC3, 0x00, b'C',
+                b'X'
+            ]
+        );
+    }
+}
@@ -56,6 +56,39 @@ interface Telemetry {
   cmd_echo: string;
 }
 
+interface TelemetryBatch {
+  connection_id: string;
+  sequence: number;
+  packets: Telemetry[];
+}
+
+interface PayloadTelemetry {
+  team_id: number;
+  mission_time: string;
+  packet_count: number;
+  packet_type: string;
+  altitude: number;
+  temperature: number;
+  voltage: number;
+  gyro_r: number;
+  gyro_p: number;
+  gyro_y: number;
+  accel_r: number;
+  accel_p: number;
+  accel_y: number;
+  mag_r: number;
+  mag_p: number;
+  mag_y: number;
+  pointing_error: number;
+  state: string;
+}
+
+interface PayloadBatch {
+  connection_id: string;
+  sequence: number;
+  packets: PayloadTelemetry[];
+}
+
 interface GraphData {
   time: string[];
   altitude: number[];
@@ -90,6 +123,71 @@ const DisplayLabel = ({ title, value }: DisplayLabelProps) => {
   );
 };
 
+interface SerialPortInfo {
+  name: string;
+  port_type: "usb" | "pci" | "bluetooth" | "unknown";
+  vid: number | null;
+  pid: number | null;
+  serial_number: string | null;
+  manufacturer: string | null;
+  product: string | null;
+}
+
+interface PortsChangedEvent {
+  ports: SerialPortInfo[];
+  added: SerialPortInfo[];
+  removed: SerialPortInfo[];
+  reappeared: SerialPortInfo | null;
+}
+
+interface TransmitStatus {
+  frame_id: number;
+  delivered: boolean;
+  status: number;
+  description: string;
+  retries: number | null;
+  command: string | null;
+}
+
+interface ConnectionStatus {
+  id: string;
+  device: string;
+  baudrate: number;
+  framing: "transparent" | "api" | "api_escaped";
+  primary: boolean;
+  connected_at: string;
+  packets_received: number;
+  duplicates: number;
+  packets_rejected: number;
+  last_packet_utc: string | null;
+  last_rssi_dbm: number | null;
+  link_status: "healthy" | "degraded" | "silent" | null;
+}
+
+interface SessionManifest {
+  id: string;
+  name: string;
+  started_at: string;
+  ended_at: string | null;
+  packets_received: number;
+}
+
+interface AntennaPointing {
+  range_m: number;
+  ground_distance_m: number;
+  bearing_deg: number;
+  elevation_deg: number;
+  station_fix_age_s: number;
+  probe_fix_age_s: number | null;
+}
+
+function describePort(port: SerialPortInfo): string {
+  const details = [port.product ?? port.manufacturer, port.serial_number && `S/N ${port.serial_number}`]
+    .filter(Boolean)
+    .join(", ");
+  return details ? `${port.name} (${details})` : port.name;
+}
+
 interface ButtonProps {
   text: string;
   onClick?: () => void;
@@ -126,8 +224,9 @@ function App() {
     tilty: [],
   });
   const [latestTelemetry, setLatestTelemetry] = useState<Telemetry | null>(null);
+  const [latestPayload, setLatestPayload] = useState<PayloadTelemetry | null>(null);
 
-  const [devices, setDevices] = useState<string[]>([]);
+  const [devices, setDevices] = useState<SerialPortInfo[]>([]);
   const [selectedDevice, setSelectedDevice] = useState<string>("");
   const [selectedBaudRate, setSelectedBaudRate] = useState<number>(115200);
   const [isConnected, setIsConnected] = useState<boolean>(false);
@@ -144,7 +243,7 @@ function App() {
   async function fetchDevices() {
     console.log("Trying to fetch devices");
     try {
-      const deviceList = await invoke<string[]>("get_serial_ports_command");
+      const deviceList = await invoke<SerialPortInfo[]>("get_serial_ports_command");
       setDevices(deviceList);
     } catch (error) {
       console.error("Failed to fetch devices:", error);
@@ -167,6 +266,156 @@ function App() {
     fetchDevices();
   }, []);
 
+  // Effects run twice in development, the prompt must not
+  const recoveryOffered = useRef(false);
+  useEffect(() => {
+    if (recoveryOffered.current) {
+      return;
+    }
+    recoveryOffered.current = true;
+    async function offerRecovery() {
+      try {
+        const session = await invoke<SessionManifest | null>("get_recoverable_session");
+        if (!session) {
+          return;
+        }
+        const description = `${session.name} (${session.packets_received} packets, started ${session.started_at})`;
+        const recover = await dialog.ask(
+          `The session ${description} was not finished. Reload its telemetry?`,
+          "Unfinished session"
+        );
+        if (!recover) {
+          await invoke("discard_recoverable_session", { id: session.id });
+          return;
+        }
+        const keepAppending = await dialog.ask(
+          "Keep appending to this session on the next connection?",
+          "Unfinished session"
+        );
+        const recovered = await invoke<number>("recover_session", {
+          id: session.id,
+          keep_appending: keepAppending,
+        });
+        console.log(`Recovered ${recovered} packets of session ${session.name}`);
+      } catch (error) {
+        console.error("Failed to recover the unfinished session:", error);
+      }
+    }
+    offerRecovery();
+  }, []);
+
+  useEffect(() => {
+    const portsListener = listen<PortsChangedEvent>("ports-changed", (event) => {
+      const { ports, added, removed, reappeared } = event.payload;
+      added.forEach((port) => console.log(`Device plugged in: ${describePort(port)}`));
+      removed.forEach((port) => console.log(`Device removed: ${describePort(port)}`));
+      setDevices(ports);
+      if (reappeared) {
+        setSelectedDevice(reappeared.name);
+      }
+    });
+    return () => {
+      portsListener.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    // A loaded or recovered flight replaces the plotted telemetry
+    const historyListener = listen<Telemetry[]>("telemetry-history", (event) => {
+      const packets = event.payload;
+      setGraphData({
+        time: packets.map((t) => t.mission_time),
+        altitude: packets.map((t) => t.altitude),
+        temperature: packets.map((t) => t.temperature),
+        pressure: packets.map((t) => t.pressure),
+        voltage: packets.map((t) => t.voltage),
+        tiltx: packets.map((t) => t.tilt_x),
+        tilty: packets.map((t) => t.tilt_y),
+      });
+      if (packets.length > 0) {
+        const telemetry = packets[packets.length - 1];
+        setLatestTelemetry(telemetry);
+        setGpsPosition([telemetry.gps_latitude, telemetry.gps_longitude]);
+      }
+      console.log(`Loaded ${packets.length} packets`);
+    });
+    return () => {
+      historyListener.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const payloadListener = listen<PayloadBatch>("payload-data", (event) => {
+      const packets = event.payload.packets;
+      if (packets.length === 0) {
+        return;
+      }
+      setLatestPayload(packets[packets.length - 1]);
+      for (const p of packets) {
+        console.log(
+          `Payload: ${p.mission_time} #${p.packet_count} ${p.state}, altitude ${p.altitude} m, ` +
+            `pointing error ${p.pointing_error}°`
+        );
+      }
+    });
+    // A recovered session replaces the payload packets
+    const payloadHistoryListener = listen<PayloadTelemetry[]>("payload-history", (event) => {
+      const packets = event.payload;
+      setLatestPayload(packets.length > 0 ? packets[packets.length - 1] : null);
+      console.log(`Loaded ${packets.length} payload packets`);
+    });
+    return () => {
+      payloadListener.then((unlisten) => unlisten());
+      payloadHistoryListener.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const transmitListener = listen<TransmitStatus>("transmit-status", (event) => {
+      const { command, delivered, description } = event.payload;
+      const name = command ?? `Frame ${event.payload.frame_id}`;
+      if (delivered) {
+        console.log(`${name} delivered`);
+      } else {
+        console.error(`${name} not delivered: ${description}`);
+      }
+    });
+    return () => {
+      transmitListener.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const connectionsListener = listen<ConnectionStatus[]>("connections-changed", (event) => {
+      event.payload.forEach((connection) =>
+        console.log(
+          `${connection.id}${connection.primary ? " (primary)" : ""} on ${connection.device}: ` +
+            `${connection.packets_received} packets, ${connection.duplicates} duplicates, ` +
+            `RSSI ${connection.last_rssi_dbm ?? "?"} dBm, link ${connection.link_status ?? "unknown"}`
+        )
+      );
+    });
+    return () => {
+      connectionsListener.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const pointingListener = listen<AntennaPointing>("antenna-pointing", (event) => {
+      const { range_m, bearing_deg, elevation_deg, station_fix_age_s, probe_fix_age_s } =
+        event.payload;
+      const probeAge = probe_fix_age_s === null ? "unknown" : `${probe_fix_age_s.toFixed(1)} s`;
+      console.log(
+        `Antenna: bearing ${bearing_deg.toFixed(1)}°, elevation ${elevation_deg.toFixed(1)}°, ` +
+          `range ${range_m.toFixed(0)} m, fix age station ${station_fix_age_s.toFixed(1)} s, ` +
+          `probe ${probeAge}`
+      );
+    });
+    return () => {
+      pointingListener.then((unlisten) => unlisten());
+    };
+  }, []);
+
   const sendMessage = async () => {
     console.log(`Sending '${message}' message...`);
     await invoke('send_message_to_device', { message })
@@ -218,23 +467,40 @@ function App() {
       setIsConnected(true);
       setIsRecording(true);
 
+      // Batches are numbered from 0 for every connection
+      const lastSequences = new Map<string, number>();
       const graphDataListener = listen(
         "graph-data",
-        ({ payload: telemetry }: { payload: Telemetry }) => {
+        ({ payload: batch }: { payload: TelemetryBatch }) => {
+          const lastSequence = lastSequences.get(batch.connection_id) ?? -1;
+          if (batch.sequence > lastSequence + 1) {
+            console.warn(
+              `Missed ${batch.sequence - lastSequence - 1} telemetry batches of ${batch.connection_id}`
+            );
+          }
+          lastSequences.set(batch.connection_id, batch.sequence);
+
+          const packets = batch.packets;
+          if (packets.length === 0) {
+            return;
+          }
+          const telemetry = packets[packets.length - 1];
           setLatestTelemetry(telemetry);
           setGraphData((old) => ({
-            time: [...old.time, telemetry.mission_time],
-            altitude: [...old.altitude, telemetry.altitude],
-            temperature: [...old.temperature, telemetry.temperature],
-            pressure: [...old.pressure, telemetry.pressure],
-            voltage: [...old.voltage, telemetry.voltage],
-            tiltx: [...old.tiltx, telemetry.tilt_x],
-            tilty: [...old.tilty, telemetry.tilt_y],
+            time: [...old.time, ...packets.map((t) => t.mission_time)],
+            altitude: [...old.altitude, ...packets.map((t) => t.altitude)],
+            temperature: [...old.temperature, ...packets.map((t) => t.temperature)],
+            pressure: [...old.pressure, ...packets.map((t) => t.pressure)],
+            voltage: [...old.voltage, ...packets.map((t) => t.voltage)],
+            tiltx: [...old.tiltx, ...packets.map((t) => t.tilt_x)],
+            tilty: [...old.tilty, ...packets.map((t) => t.tilt_y)],
           }));
           setGpsPosition([telemetry.gps_latitude, telemetry.gps_longitude]);
-          const telemetryString = `${telemetry.team_id},${telemetry.mission_time},${telemetry.packet_count},${telemetry.mode},${telemetry.state},${telemetry.altitude},${telemetry.hs_deployed},${telemetry.pc_deployed},${telemetry.mast_raised},${telemetry.temperature},${telemetry.pressure},${telemetry.voltage},${telemetry.gps_time},${telemetry.gps_altitude},${telemetry.gps_latitude},${telemetry.gps_longitude},${telemetry.gps_sats},${telemetry.tilt_x},${telemetry.tilt_y},${telemetry.cmd_echo}`;
+          for (const t of packets) {
+            const telemetryString = `${t.team_id},${t.mission_time},${t.packet_count},${t.mode},${t.state},${t.altitude},${t.hs_deployed},${t.pc_deployed},${t.mast_raised},${t.temperature},${t.pressure},${t.voltage},${t.gps_time},${t.gps_altitude},${t.gps_latitude},${t.gps_longitude},${t.gps_sats},${t.tilt_x},${t.tilt_y},${t.cmd_echo}`;
 
-          console.log(`Received: ${telemetryString}`);
+            console.log(`Received: ${telemetryString}`);
+          }
         }
       );
 
@@ -489,6 +755,8 @@ function App() {
           <DisplayLabel title="PC Deployed" value={latestTelemetry?.pc_deployed.toString() || '0.0'} />
           <DisplayLabel title="Tilt X" value={latestTelemetry ? latestTelemetry.tilt_x.toFixed(2) : '0.00'} />
           <DisplayLabel title="Tilt Y" value={latestTelemetry ? latestTelemetry.tilt_y.toFixed(2) : '0.00'} />
+          <DisplayLabel title="Payload state" value={latestPayload?.state || ''} />
+          <DisplayLabel title="Pointing error" value={latestPayload ? latestPayload.pointing_error.toFixed(1) : '0.0'} />
         </div>
         {/* Third Column */}
         <div>
@@ -509,8 +777,8 @@ function App() {
           <select value={selectedDevice} onChange={handleDeviceChange} disabled={(isConnected || (!isFlightMode && !isSimulationMode))}>
             <option value="" disabled>Pick a device</option>
             {devices.map((device) => (
-              <option key={device} value={device}>
-                {device}
+              <option key={device.name} value={device.name}>
+                {describePort(device)}
               </option>
             ))}
           </select>